@@ -0,0 +1,26 @@
+use std::process::Command;
+
+/// Records the target triple and, when built from a git checkout, the commit
+/// for `perplexica_version`.
+fn main() {
+    println!(
+        "cargo:rustc-env=PERPLEXICA_BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=PERPLEXICA_GIT_COMMIT={}", commit);
+
+    // A missing path would rerun the script on every build.
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
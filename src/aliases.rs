@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+/// What an alias stands for: a provider, and optionally one of its model keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    provider_id: String,
+    key: Option<String>,
+}
+
+/// Friendly names for providers and models from `PERPLEXICA_MODEL_ALIASES`.
+///
+/// The variable is a comma-separated list of `name=provider:key` model aliases
+/// and `name=provider` provider aliases. Only the first `:` separates provider
+/// from key, so keys like `llama3:8b` work. Values that aren't aliases are
+/// passed through unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ModelAliases(HashMap<String, Target>);
+
+impl ModelAliases {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        let mut aliases = HashMap::new();
+
+        for entry in value.unwrap_or_default().split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                format!(
+                    "PERPLEXICA_MODEL_ALIASES entry '{}' must be name=provider or name=provider:key",
+                    entry
+                )
+            };
+
+            let (name, target) = entry.split_once('=').ok_or_else(invalid)?;
+            let (provider_id, key) = match target.split_once(':') {
+                Some((provider_id, key)) => (provider_id.trim(), Some(key.trim())),
+                None => (target.trim(), None),
+            };
+            if name.trim().is_empty() || provider_id.is_empty() || key == Some("") {
+                return Err(invalid());
+            }
+
+            aliases.insert(
+                name.trim().to_string(),
+                Target {
+                    provider_id: provider_id.to_string(),
+                    key: key.map(String::from),
+                },
+            );
+        }
+
+        Ok(Self(aliases))
+    }
+
+    /// Resolves a `provider_id` value; any alias stands for its provider.
+    pub fn provider(&self, value: String) -> String {
+        match self.0.get(&value) {
+            Some(target) => target.provider_id.clone(),
+            None => value,
+        }
+    }
+
+    /// Resolves a model key to `(provider_id, key)`. A model alias brings its
+    /// own provider; anything else keeps `provider_id`.
+    pub fn model(&self, key: String, provider_id: &str) -> (String, String) {
+        match self.0.get(&key) {
+            Some(Target {
+                provider_id,
+                key: Some(model_key),
+            }) => (provider_id.clone(), model_key.clone()),
+            _ => (provider_id.to_string(), key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases() -> ModelAliases {
+        ModelAliases::parse(Some(
+            "fast-model=openai:gpt-4o-mini, local=ollama:llama3:8b,work=550e8400",
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_aliases_resolve_to_provider_and_key() {
+        let aliases = aliases();
+
+        assert_eq!(
+            aliases.model("fast-model".to_string(), "anthropic"),
+            ("openai".to_string(), "gpt-4o-mini".to_string())
+        );
+        assert_eq!(
+            aliases.model("local".to_string(), "anthropic"),
+            ("ollama".to_string(), "llama3:8b".to_string())
+        );
+        assert_eq!(aliases.provider("work".to_string()), "550e8400");
+        assert_eq!(aliases.provider("fast-model".to_string()), "openai");
+    }
+
+    #[test]
+    fn test_non_aliases_pass_through() {
+        let aliases = aliases();
+
+        assert_eq!(
+            aliases.model("gpt-4".to_string(), "openai"),
+            ("openai".to_string(), "gpt-4".to_string())
+        );
+        // A provider alias has no model key to stand in for.
+        assert_eq!(
+            aliases.model("work".to_string(), "openai"),
+            ("openai".to_string(), "work".to_string())
+        );
+        assert_eq!(aliases.provider("openai".to_string()), "openai");
+        assert_eq!(
+            ModelAliases::default().provider("fast-model".to_string()),
+            "fast-model"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_entries() {
+        for value in ["fast-model", "=openai:gpt-4", "fast=:gpt-4", "fast=openai:"] {
+            assert!(ModelAliases::parse(Some(value)).is_err(), "{}", value);
+        }
+        assert!(ModelAliases::parse(Some(" , ")).unwrap().0.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BREAKER_THRESHOLD: u32 = 0;
+pub const DEFAULT_BREAKER_WINDOW_MS: u64 = 60_000;
+pub const DEFAULT_BREAKER_COOLDOWN_MS: u64 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Requests go through. `failures` consecutive failures have been seen
+    /// since `since`.
+    Closed { failures: u32, since: Instant },
+    /// Requests are refused until `until`.
+    Open { until: Instant },
+    /// One probe, started at `probe_started`, decides whether to close again.
+    HalfOpen { probe_started: Instant },
+}
+
+/// Stops sending requests to a backend that keeps failing.
+///
+/// After `threshold` consecutive failures, each within `window` of the first,
+/// the circuit opens and every request is refused for `cooldown`. The first
+/// request after that is let through as a probe while the others are still
+/// refused: success closes the circuit, failure opens it for another cooldown.
+/// A probe that never reports back is replaced after a cooldown. A zero
+/// threshold disables the breaker.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            cooldown,
+            state: Mutex::new(State::Closed {
+                failures: 0,
+                since: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Whether a request may be sent now; when not, how long until the
+    /// circuit half-opens.
+    pub fn check(&self) -> Result<(), Duration> {
+        if self.threshold == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(until - now),
+            State::HalfOpen { probe_started } if now < probe_started + self.cooldown => {
+                Err(probe_started + self.cooldown - now)
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                *state = State::HalfOpen { probe_started: now };
+                tracing::info!("circuit breaker half-open; probing the backend");
+                Ok(())
+            }
+        }
+    }
+
+    /// Records a response from the backend, closing the circuit.
+    pub fn record_success(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen { .. }) {
+            tracing::info!("circuit breaker closed; the backend recovered");
+        }
+        *state = State::Closed {
+            failures: 0,
+            since: Instant::now(),
+        };
+    }
+
+    /// Records a failed request, opening the circuit once the failures reach
+    /// the threshold or a probe fails.
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let (failures, since) = match *state {
+            State::Closed { failures, since }
+                if failures > 0 && now.duration_since(since) <= self.window =>
+            {
+                (failures + 1, since)
+            }
+            State::Closed { .. } => (1, now),
+            // Requests refused while open don't reach the backend; this is
+            // one that was sent before the circuit opened.
+            State::Open { .. } => return,
+            State::HalfOpen { .. } => (self.threshold, now),
+        };
+
+        if failures >= self.threshold {
+            tracing::warn!(
+                failures,
+                cooldown_ms = self.cooldown.as_millis() as u64,
+                "circuit breaker open; refusing requests to the backend"
+            );
+            *state = State::Open {
+                until: now + self.cooldown,
+            };
+        } else {
+            *state = State::Closed { failures, since };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(
+            threshold,
+            Duration::from_secs(60),
+            Duration::from_millis(30),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_closed_open_half_open_closed() {
+        let breaker = breaker(2);
+
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        let wait = breaker.check().unwrap_err();
+        assert!(wait <= Duration::from_millis(30));
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        // The probe goes through; everyone else waits for its outcome.
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_err());
+
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_reopens() {
+        let breaker = breaker(1);
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        // A probe that never reports back is replaced after a cooldown.
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(breaker.check().is_ok());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_successes_and_old_failures_reset_the_count() {
+        let reset = breaker(2);
+        reset.record_failure();
+        reset.record_success();
+        reset.record_failure();
+        assert!(reset.check().is_ok());
+
+        let windowed = CircuitBreaker::new(2, Duration::from_millis(20), Duration::from_secs(60));
+        windowed.record_failure();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        windowed.record_failure();
+        assert!(windowed.check().is_ok());
+
+        let disabled = breaker(0);
+        for _ in 0..10 {
+            disabled.record_failure();
+        }
+        assert!(disabled.check().is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::coalesce::SingleFlight;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A single cached value with a time-to-live.
+///
+/// Refreshes are single-flight: the first caller to find the value missing or
+/// expired runs the refresh, and concurrent callers wait for it and share its
+/// outcome, an error included, instead of fetching again. No lock is held
+/// while the refresh runs, and a failed refresh is not cached. A zero TTL
+/// disables caching, and callers refresh independently.
+#[derive(Debug)]
+pub struct TtlCell<T, E> {
+    ttl: Duration,
+    state: Mutex<Option<(Instant, Arc<T>)>>,
+    refreshes: SingleFlight<Result<Arc<T>, E>>,
+}
+
+impl<T, E: Clone> TtlCell<T, E> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Mutex::new(None),
+            refreshes: SingleFlight::new(true),
+        }
+    }
+
+    pub async fn get_or_refresh<F, Fut>(&self, refresh: F) -> Result<Arc<T>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if self.ttl.is_zero() {
+            return refresh().await.map(Arc::new);
+        }
+        if let Some(value) = self.fresh() {
+            return Ok(value);
+        }
+
+        self.refreshes
+            .run(String::new(), || async {
+                // A refresh that finished since the check above has already
+                // stored the value.
+                if let Some(value) = self.fresh() {
+                    return Ok(value);
+                }
+                let value = Arc::new(refresh().await?);
+                *self.state.lock().unwrap() = Some((Instant::now(), value.clone()));
+                Ok(value)
+            })
+            .await
+    }
+
+    fn fresh(&self) -> Option<Arc<T>> {
+        match self.state.lock().unwrap().as_ref() {
+            Some((fetched_at, value)) if fetched_at.elapsed() < self.ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Values by key, each kept for a time-to-live.
+///
+/// When the cache is full, an insert evicts the least recently used entry.
+/// Eviction scans every entry, which is fine for the few hundred a session
+/// repeats. A zero capacity disables caching.
+#[derive(Debug)]
+pub struct LruCache<T> {
+    capacity: usize,
+    ttl: Duration,
+    state: std::sync::Mutex<LruState<T>>,
+}
+
+#[derive(Debug)]
+struct LruState<T> {
+    entries: HashMap<String, LruEntry<T>>,
+    /// Bumped on every use, so entries can be ordered by their last one.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct LruEntry<T> {
+    inserted: Instant,
+    last_used: u64,
+    value: T,
+}
+
+impl<T: Clone> LruCache<T> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: std::sync::Mutex::new(LruState {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the value for `key` unless it is missing or has expired.
+    pub fn get(&self, key: &str) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let entry = state.entries.get_mut(key)?;
+        if entry.inserted.elapsed() >= self.ttl {
+            state.entries.remove(key);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&self, key: String, value: T) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            state
+                .entries
+                .retain(|_, entry| entry.inserted.elapsed() < ttl);
+            if state.entries.len() >= self.capacity
+                && let Some(oldest) = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+            {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(
+            key,
+            LruEntry {
+                inserted: Instant::now(),
+                last_used: clock,
+                value,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_expired_value_refreshes_once_for_concurrent_callers() {
+        let cell = Arc::new(TtlCell::new(Duration::from_millis(20)));
+        let refreshes = Arc::new(AtomicUsize::new(0));
+
+        let refresh = |refreshes: Arc<AtomicUsize>| async move {
+            refreshes.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, ()>(refreshes.load(Ordering::SeqCst))
+        };
+
+        cell.get_or_refresh(|| refresh(refreshes.clone()))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            let cell = cell.clone();
+            let refreshes = refreshes.clone();
+            tasks.spawn(async move { *cell.get_or_refresh(|| refresh(refreshes)).await.unwrap() });
+        }
+        let values = tasks.join_all().await;
+
+        assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+        assert!(values.iter().all(|&value| value == 2));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_callers_share_a_failed_refresh() {
+        let cell = Arc::new(TtlCell::new(Duration::from_secs(60)));
+        let refreshes = Arc::new(AtomicUsize::new(0));
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            let cell = cell.clone();
+            let refreshes = refreshes.clone();
+            tasks.spawn(async move {
+                cell.get_or_refresh(|| async move {
+                    refreshes.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Err::<u32, _>("down")
+                })
+                .await
+            });
+        }
+        let results = tasks.join_all().await;
+
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| *result == Err("down")));
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_disables_caching() {
+        let cell = TtlCell::new(Duration::ZERO);
+        let refreshes = AtomicUsize::new(0);
+
+        for _ in 0..3 {
+            cell.get_or_refresh(|| async { Ok::<_, ()>(refreshes.fetch_add(1, Ordering::SeqCst)) })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(refreshes.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_lru_cache_evicts_least_recently_used() {
+        let cache = LruCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        assert_eq!(cache.get("a"), Some(1));
+
+        cache.insert("c".to_string(), 3);
+
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn test_lru_cache_expires_entries() {
+        let cache = LruCache::new(2, Duration::from_millis(20));
+        cache.insert("a".to_string(), 1);
+        assert_eq!(cache.get("a"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(cache.get("a"), None);
+        let disabled = LruCache::new(0, Duration::from_secs(60));
+        disabled.insert("a".to_string(), 1);
+        assert_eq!(disabled.get("a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_not_cached() {
+        let cell = TtlCell::new(Duration::from_secs(60));
+
+        let error = cell
+            .get_or_refresh(|| async { Err::<u32, _>("down") })
+            .await;
+        let value = cell.get_or_refresh(|| async { Ok::<_, &str>(7) }).await;
+
+        assert_eq!(error, Err("down"));
+        assert_eq!(value.map(|v| *v), Ok(7));
+    }
+}
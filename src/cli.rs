@@ -0,0 +1,182 @@
+use crate::perplexica_service::{PerplexicaSearchRequest, PerplexicaService};
+use clap::{Args, Parser, Subcommand};
+use std::borrow::Cow;
+use std::io::Write;
+
+/// Command-line arguments. With no subcommand the server speaks MCP on the
+/// configured transport.
+#[derive(Debug, Parser)]
+#[command(
+    name = "perplexica-mcp",
+    about = "MCP server for Perplexica search",
+    disable_version_flag = true
+)]
+pub struct Cli {
+    /// Print version and build information, then exit
+    #[arg(long)]
+    pub version: bool,
+
+    /// Validate the configuration, print a PASS/FAIL line per setting to
+    /// stderr, then exit without starting the server
+    #[arg(long)]
+    pub check_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run one search, print its markdown result and exit, without MCP
+    Search(SearchArgs),
+}
+
+/// Flags for `search`. Anything left out falls back to the environment, as
+/// for a `perplexica_search` call.
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// The search query; several words are joined with spaces
+    #[arg(required = true, num_args = 1..)]
+    pub query: Vec<String>,
+
+    /// Focus mode, e.g. webSearch or academicSearch
+    #[arg(long)]
+    pub focus_mode: Option<String>,
+
+    /// Provider ID (default: PERPLEXICA_PROVIDER_ID)
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// Chat model key (default: PERPLEXICA_CHAT_MODEL_KEY)
+    #[arg(long)]
+    pub chat_model: Option<String>,
+
+    /// Embedding model key (default: PERPLEXICA_EMBEDDING_MODEL_KEY)
+    #[arg(long)]
+    pub embedding_model: Option<String>,
+
+    /// Optimization mode: speed, balanced or quality
+    #[arg(long)]
+    pub optimization_mode: Option<String>,
+}
+
+impl SearchArgs {
+    fn request(self) -> PerplexicaSearchRequest {
+        let defaults = PerplexicaSearchRequest::default();
+        PerplexicaSearchRequest {
+            query: self.query.join(" "),
+            focus_mode: self
+                .focus_mode
+                .map(Cow::Owned)
+                .unwrap_or(defaults.focus_mode),
+            provider_id: self.provider,
+            chat_model_key: self.chat_model,
+            embedding_model_key: self.embedding_model,
+            optimization_mode: self.optimization_mode,
+            ..defaults
+        }
+    }
+}
+
+/// Runs the `search` command, writing the markdown result to `out`.
+pub async fn search(
+    service: &PerplexicaService,
+    args: SearchArgs,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let markdown = service
+        .search_once(args.request())
+        .await
+        .map_err(|error| anyhow::anyhow!(error.message))?;
+    writeln!(out, "{}", markdown.trim_end())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PerplexicaConfig;
+    use crate::test_support::{MockResponse, MockServer};
+
+    #[test]
+    fn test_parse_search_command() {
+        let cli = Cli::try_parse_from([
+            "perplexica-mcp",
+            "search",
+            "what",
+            "is",
+            "rust",
+            "--focus-mode",
+            "academicSearch",
+            "--provider",
+            "openai",
+        ])
+        .unwrap();
+        let Some(Command::Search(args)) = cli.command else {
+            panic!("expected a search command");
+        };
+        let request = args.request();
+        assert_eq!(request.query, "what is rust");
+        assert_eq!(request.focus_mode, "academicSearch");
+        assert_eq!(request.provider_id.as_deref(), Some("openai"));
+        assert!(request.chat_model_key.is_none());
+
+        let cli = Cli::try_parse_from(["perplexica-mcp"]).unwrap();
+        assert!(cli.command.is_none() && !cli.version && !cli.check_config);
+        assert!(
+            Cli::try_parse_from(["perplexica-mcp", "--check-config"])
+                .unwrap()
+                .check_config
+        );
+        assert!(
+            Cli::try_parse_from(["perplexica-mcp", "--version"])
+                .unwrap()
+                .version
+        );
+        assert!(Cli::try_parse_from(["perplexica-mcp", "search"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search_prints_markdown() {
+        let server = MockServer::start(|_| {
+            MockResponse::new(
+                200,
+                r#"{"message": "Rust is a language [1].", "sources": [
+                    {"pageContent": "", "metadata": {"title": "Rust", "url": "https://www.rust-lang.org"}}
+                ]}"#,
+            )
+        })
+        .await;
+        let service = PerplexicaService::with_config(PerplexicaConfig::from_iter([
+            ("PERPLEXICA_API_URL", server.url()),
+            ("PERPLEXICA_PROVIDER_ID", "openai"),
+            ("PERPLEXICA_EMBEDDING_MODEL_KEY", "text-embedding-3-small"),
+        ]))
+        .unwrap();
+        let args = Cli::try_parse_from([
+            "perplexica-mcp",
+            "search",
+            "What is Rust?",
+            "--chat-model",
+            "gpt-4o",
+        ])
+        .unwrap()
+        .command
+        .map(|Command::Search(args)| args)
+        .unwrap();
+
+        let mut stdout = Vec::new();
+        search(&service, args, &mut stdout).await.unwrap();
+
+        let stdout = String::from_utf8(stdout).unwrap();
+        assert!(
+            stdout.starts_with("## Summary\n\nRust is a language [1].\n"),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("1. Rust — https://www.rust-lang.org"));
+        let sent: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        assert_eq!(sent["query"], "What is Rust?");
+        assert_eq!(sent["chatModel"]["key"], "gpt-4o");
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// How much a query is canonicalized before it becomes part of a dedup key, so
+/// trivially different queries share a key. The query sent upstream is never
+/// changed. Each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyNormalization {
+    /// Queries must match exactly.
+    #[default]
+    None,
+    /// Trim and collapse runs of whitespace.
+    Whitespace,
+    /// Also lowercase.
+    Case,
+    /// Also strip trailing sentence punctuation (`.?!,;:`), so "What is AI?"
+    /// matches "what is ai".
+    Full,
+}
+
+impl KeyNormalization {
+    pub const VALID_VALUES: &'static [&'static str] = &["none", "whitespace", "case", "full"];
+
+    pub fn apply(self, query: &str) -> String {
+        if self == Self::None {
+            return query.to_string();
+        }
+        let mut key = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        if matches!(self, Self::Case | Self::Full) {
+            key = key.to_lowercase();
+        }
+        if self == Self::Full {
+            key = key
+                .trim_end_matches(['.', '?', '!', ',', ';', ':', ' '])
+                .to_string();
+        }
+        key
+    }
+}
+
+impl FromStr for KeyNormalization {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "whitespace" => Ok(Self::Whitespace),
+            "case" => Ok(Self::Case),
+            "full" => Ok(Self::Full),
+            _ => Err(format!(
+                "PERPLEXICA_KEY_NORMALIZATION must be one of {}, got '{}'",
+                Self::VALID_VALUES.join(", "),
+                value
+            )),
+        }
+    }
+}
+
+/// When a burst opened, and the result its calls share.
+type Burst<T> = (Instant, Arc<OnceCell<T>>);
+
+/// Collapses bursts of identical calls onto one.
+///
+/// The first call for a key opens a window; calls with the same key that
+/// arrive before it closes wait for and share the first call's result instead
+/// of running their own. Once the window has passed, the next call runs again.
+/// Nothing outlives the window, so this is not a cache. A zero window disables
+/// coalescing.
+#[derive(Debug)]
+pub struct Coalescer<T> {
+    window: Duration,
+    bursts: Mutex<HashMap<String, Burst<T>>>,
+}
+
+impl<T: Clone> Coalescer<T> {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            bursts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    pub async fn run<F, Fut>(&self, key: String, call: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        if !self.is_enabled() {
+            return call().await;
+        }
+
+        let cell = {
+            let mut bursts = self.bursts.lock().unwrap();
+            bursts.retain(|_, (opened, _)| opened.elapsed() < self.window);
+            bursts
+                .entry(key)
+                .or_insert_with(|| (Instant::now(), Arc::new(OnceCell::new())))
+                .1
+                .clone()
+        };
+
+        // If the first caller is cancelled, a waiting caller runs its own call.
+        cell.get_or_init(call).await.clone()
+    }
+}
+
+/// Shares a call with identical calls made while it is still running.
+///
+/// Calls with the key of one in flight wait for and share its result instead
+/// of running their own. Unlike [`Coalescer`] there is no window: an entry
+/// lasts exactly as long as its call, and the next call runs again.
+#[derive(Debug)]
+pub struct SingleFlight<T> {
+    enabled: bool,
+    pending: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub async fn run<F, Fut>(&self, key: String, call: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        if !self.enabled {
+            return call().await;
+        }
+
+        let cell = self
+            .pending
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let flight = Flight {
+            pending: &self.pending,
+            key,
+            cell,
+        };
+
+        // As with Coalescer, a waiting caller takes over from a cancelled one.
+        flight.cell.get_or_init(call).await.clone()
+    }
+}
+
+/// One caller's share of an entry in [`SingleFlight`], removing the entry
+/// when the call has finished or its last caller has gone.
+struct Flight<'a, T> {
+    pending: &'a Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+    key: String,
+    cell: Arc<OnceCell<T>>,
+}
+
+impl<T> Drop for Flight<'_, T> {
+    fn drop(&mut self) {
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        // The map holds one reference and each caller another.
+        if let Some(current) = pending.get(&self.key)
+            && Arc::ptr_eq(current, &self.cell)
+            && (self.cell.initialized() || Arc::strong_count(&self.cell) == 2)
+        {
+            pending.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_calls_after_window_run_again() {
+        let coalescer = Coalescer::new(Duration::from_millis(20));
+        let calls = AtomicUsize::new(0);
+        let call = || async { calls.fetch_add(1, Ordering::SeqCst) };
+
+        assert_eq!(coalescer.run("a".to_string(), call).await, 0);
+        assert_eq!(coalescer.run("a".to_string(), call).await, 0);
+        assert_eq!(coalescer.run("b".to_string(), call).await, 1);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(coalescer.run("a".to_string(), call).await, 2);
+    }
+
+    #[test]
+    fn test_key_normalization_levels() {
+        let query = "  What   is\tAI?! ";
+
+        assert_eq!(KeyNormalization::None.apply(query), query);
+        assert_eq!(KeyNormalization::Whitespace.apply(query), "What is AI?!");
+        assert_eq!(KeyNormalization::Case.apply(query), "what is ai?!");
+        assert_eq!(KeyNormalization::Full.apply(query), "what is ai");
+        assert_eq!(
+            KeyNormalization::Full.apply("what is ai"),
+            KeyNormalization::Full.apply("What is AI?")
+        );
+        // Symbols that are part of a term are kept.
+        assert_eq!(KeyNormalization::Full.apply("C++ vs C#?"), "c++ vs c#");
+        assert!("aggressive".parse::<KeyNormalization>().is_err());
+        assert_eq!(" Full ".parse(), Ok(KeyNormalization::Full));
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_shares_only_pending_calls() {
+        let flights = SingleFlight::new(true);
+        let calls = AtomicUsize::new(0);
+        let call = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            calls.fetch_add(1, Ordering::SeqCst)
+        };
+
+        let (a, b, c) = tokio::join!(
+            flights.run("a".to_string(), call),
+            flights.run("a".to_string(), call),
+            flights.run("b".to_string(), call),
+        );
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(flights.pending.lock().unwrap().is_empty());
+
+        assert_eq!(flights.run("a".to_string(), call).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_forgets_cancelled_calls() {
+        let flights = SingleFlight::new(true);
+        let call = flights.run("a".to_string(), std::future::pending::<u32>);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), call)
+                .await
+                .is_err()
+        );
+        assert!(flights.pending.lock().unwrap().is_empty());
+
+        let disabled = SingleFlight::new(false);
+        let calls = AtomicUsize::new(0);
+        let call = || async { calls.fetch_add(1, Ordering::SeqCst) };
+        let (a, b) = tokio::join!(
+            disabled.run("a".to_string(), call),
+            disabled.run("a".to_string(), call)
+        );
+        assert_ne!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_zero_window_disables_coalescing() {
+        let coalescer = Coalescer::new(Duration::ZERO);
+        let calls = AtomicUsize::new(0);
+
+        for expected in 0..3 {
+            let value = coalescer
+                .run("a".to_string(), || async {
+                    calls.fetch_add(1, Ordering::SeqCst)
+                })
+                .await;
+            assert_eq!(value, expected);
+        }
+    }
+}
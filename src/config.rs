@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Every setting the server reads, besides the `PERPLEXICA_TOOL_DESC_<NAME>`
+/// overrides and the standard proxy variables.
+const KNOWN_SETTINGS: &[&str] = &[
+    "PERPLEXICA_ACCEPT_INVALID_CERTS",
+    "PERPLEXICA_ADDITIONAL_URLS",
+    "PERPLEXICA_ALLOW_TOKEN_OVER_HTTP",
+    "PERPLEXICA_ALLOW_URL_OVERRIDE",
+    "PERPLEXICA_API_TOKEN",
+    "PERPLEXICA_API_URL",
+    "PERPLEXICA_AUTH_HEADER",
+    "PERPLEXICA_AUTO_EMBEDDING",
+    "PERPLEXICA_BACKOFF_JITTER",
+    "PERPLEXICA_BREAKER_COOLDOWN_MS",
+    "PERPLEXICA_BREAKER_THRESHOLD",
+    "PERPLEXICA_BREAKER_WINDOW_MS",
+    "PERPLEXICA_CACHE_DIR",
+    "PERPLEXICA_CACHE_DIR_MAX_MB",
+    "PERPLEXICA_CACHE_DIR_TTL_SECS",
+    "PERPLEXICA_CACHE_SIZE",
+    "PERPLEXICA_CACHE_TTL_SECS",
+    "PERPLEXICA_CA_CERT",
+    "PERPLEXICA_CHAT_MODEL_KEY",
+    "PERPLEXICA_CHECK_CONFIG",
+    "PERPLEXICA_CHECK_ON_START",
+    "PERPLEXICA_COALESCE_MS",
+    "PERPLEXICA_CONCURRENCY_TIMEOUT_MS",
+    "PERPLEXICA_CONNECT_TIMEOUT_SECS",
+    "PERPLEXICA_CONTROL_CHARS",
+    "PERPLEXICA_COOKIE",
+    "PERPLEXICA_COOKIE_STORE",
+    "PERPLEXICA_DEFAULT_LANGUAGE",
+    "PERPLEXICA_EMBEDDING_MODEL_KEY",
+    "PERPLEXICA_EMBEDDING_PROVIDER_ID",
+    "PERPLEXICA_ENABLED_TOOLS",
+    "PERPLEXICA_ENABLE_LOADTEST",
+    "PERPLEXICA_ENV_FILE",
+    "PERPLEXICA_ERROR_BODY_CHARS",
+    "PERPLEXICA_FALLBACK_PROVIDERS",
+    "PERPLEXICA_FOCUS_MODES",
+    "PERPLEXICA_HISTORY_OPTIMIZATION",
+    "PERPLEXICA_KEY_NORMALIZATION",
+    "PERPLEXICA_LOG",
+    "PERPLEXICA_LOG_REQUESTS",
+    "PERPLEXICA_MAX_CONCURRENCY",
+    "PERPLEXICA_MAX_HISTORY_TURNS",
+    "PERPLEXICA_MAX_QUEUE_DEPTH",
+    "PERPLEXICA_MAX_REDIRECTS",
+    "PERPLEXICA_MAX_RESPONSE_CHARS",
+    "PERPLEXICA_MAX_RETRIES",
+    "PERPLEXICA_MAX_RETRIES_CEILING",
+    "PERPLEXICA_MAX_URL_CHARS",
+    "PERPLEXICA_METRICS",
+    "PERPLEXICA_MOCK",
+    "PERPLEXICA_MOCK_FILE",
+    "PERPLEXICA_MODEL_ALIASES",
+    "PERPLEXICA_OPTIMIZATION_MODE",
+    "PERPLEXICA_PAGE_CONTENT_CHARS",
+    "PERPLEXICA_POOL_IDLE_TIMEOUT_SECS",
+    "PERPLEXICA_POOL_MAX_IDLE",
+    "PERPLEXICA_PRICING_FILE",
+    "PERPLEXICA_PROFILES_FILE",
+    "PERPLEXICA_PROVIDERS_CACHE_TTL_SECS",
+    "PERPLEXICA_PROVIDERS_PATH",
+    "PERPLEXICA_PROVIDER_ID",
+    "PERPLEXICA_PROXY",
+    "PERPLEXICA_QUERY_PREFIX",
+    "PERPLEXICA_QUERY_SUFFIX",
+    "PERPLEXICA_RANK_RECENCY_WEIGHT",
+    "PERPLEXICA_REDACT",
+    "PERPLEXICA_RESULT_WEBHOOK",
+    "PERPLEXICA_RETRY_BASE_DELAY_MS",
+    "PERPLEXICA_SEARCH_DISABLED",
+    "PERPLEXICA_SEARCH_PATH",
+    "PERPLEXICA_SHARE_IN_FLIGHT",
+    "PERPLEXICA_SHUTDOWN_GRACE_SECS",
+    "PERPLEXICA_SOURCES_HEADER",
+    "PERPLEXICA_STRICT_API_URL",
+    "PERPLEXICA_SUMMARY_HEADER",
+    "PERPLEXICA_SYSTEM_INSTRUCTIONS",
+    "PERPLEXICA_TIMEOUT_OVERRIDES",
+    "PERPLEXICA_TRANSPORT",
+    "PERPLEXICA_USER_AGENT",
+    "PERPLEXICA_WS_BIND",
+];
+
+/// The standard proxy variables, also read in lowercase.
+const PROXY_VARS: &[&str] = &["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY", "NO_PROXY"];
+
+/// Settings for [`PerplexicaService::with_config`].
+///
+/// The core settings have typed builder methods; any other setting is given
+/// by the name of the environment variable it stands in for, e.g.
+/// `PERPLEXICA_MAX_RETRIES`. Values go through the same parsing and
+/// validation as the environment, so a config built in code and one read from
+/// the environment behave identically. A name the server doesn't know is
+/// rejected by `with_config`, so a typo can't be silently ignored.
+///
+/// ```ignore
+/// let config = PerplexicaConfig::default()
+///     .api_url("http://localhost:3000")
+///     .provider_id("openai")
+///     .connect_timeout(Duration::from_secs(2))
+///     .set("PERPLEXICA_MAX_RETRIES", "5");
+/// let service = PerplexicaService::with_config(config)?;
+/// ```
+///
+/// [`PerplexicaService::with_config`]: crate::perplexica_service::PerplexicaService::with_config
+#[derive(Clone, Default)]
+pub struct PerplexicaConfig {
+    api_url: Option<String>,
+    api_token: Option<String>,
+    provider_id: Option<String>,
+    chat_model_key: Option<String>,
+    embedding_model_key: Option<String>,
+    connect_timeout: Option<Duration>,
+    /// Every other setting, by variable name.
+    settings: HashMap<String, String>,
+}
+
+impl PerplexicaConfig {
+    /// Reads the settings from the process environment, including the
+    /// standard proxy variables. Variables that aren't valid Unicode are
+    /// skipped, as if unset, and unknown `PERPLEXICA_` variables are skipped
+    /// with a warning.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        for (name, value) in std::env::vars_os() {
+            let (Ok(name), Ok(value)) = (name.into_string(), value.into_string()) else {
+                continue;
+            };
+            if is_known(&name) {
+                config = config.set(name, value);
+            } else if name.starts_with("PERPLEXICA_") {
+                tracing::warn!(name = %name, "ignoring unknown PERPLEXICA_ environment variable");
+            }
+        }
+        config
+    }
+
+    /// The Perplexica instance to search, `PERPLEXICA_API_URL`.
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = Some(url.into());
+        self
+    }
+
+    /// The bearer token sent to Perplexica, `PERPLEXICA_API_TOKEN`.
+    pub fn api_token(mut self, token: impl Into<String>) -> Self {
+        self.api_token = Some(token.into());
+        self
+    }
+
+    /// The default provider, `PERPLEXICA_PROVIDER_ID`.
+    pub fn provider_id(mut self, provider_id: impl Into<String>) -> Self {
+        self.provider_id = Some(provider_id.into());
+        self
+    }
+
+    /// The default chat model, `PERPLEXICA_CHAT_MODEL_KEY`.
+    pub fn chat_model_key(mut self, key: impl Into<String>) -> Self {
+        self.chat_model_key = Some(key.into());
+        self
+    }
+
+    /// The default embedding model, `PERPLEXICA_EMBEDDING_MODEL_KEY`.
+    pub fn embedding_model_key(mut self, key: impl Into<String>) -> Self {
+        self.embedding_model_key = Some(key.into());
+        self
+    }
+
+    /// How long connecting to Perplexica may take,
+    /// `PERPLEXICA_CONNECT_TIMEOUT_SECS`, in whole seconds.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.remove("PERPLEXICA_CONNECT_TIMEOUT_SECS");
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets any setting by its variable name, replacing an earlier value. The
+    /// core settings go through their builder methods; a connect timeout
+    /// that isn't a number is kept as given, so validation reports it.
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let (name, value) = (name.into(), value.into());
+        match name.as_str() {
+            "PERPLEXICA_API_URL" => self.api_url(value),
+            "PERPLEXICA_API_TOKEN" => self.api_token(value),
+            "PERPLEXICA_PROVIDER_ID" => self.provider_id(value),
+            "PERPLEXICA_CHAT_MODEL_KEY" => self.chat_model_key(value),
+            "PERPLEXICA_EMBEDDING_MODEL_KEY" => self.embedding_model_key(value),
+            "PERPLEXICA_CONNECT_TIMEOUT_SECS" => match value.trim().parse() {
+                Ok(secs) => self.connect_timeout(Duration::from_secs(secs)),
+                Err(_) => {
+                    self.connect_timeout = None;
+                    self.settings.insert(name, value);
+                    self
+                }
+            },
+            _ => {
+                self.settings.insert(name, value);
+                self
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "PERPLEXICA_API_URL" => self.api_url.clone(),
+            "PERPLEXICA_API_TOKEN" => self.api_token.clone(),
+            "PERPLEXICA_PROVIDER_ID" => self.provider_id.clone(),
+            "PERPLEXICA_CHAT_MODEL_KEY" => self.chat_model_key.clone(),
+            "PERPLEXICA_EMBEDDING_MODEL_KEY" => self.embedding_model_key.clone(),
+            "PERPLEXICA_CONNECT_TIMEOUT_SECS" if self.connect_timeout.is_some() => self
+                .connect_timeout
+                .map(|timeout| timeout.as_secs().to_string()),
+            _ => self.settings.get(name).cloned(),
+        }
+    }
+
+    /// Fails on the first setting the server doesn't know.
+    pub(crate) fn check_names(&self) -> anyhow::Result<()> {
+        let mut unknown: Vec<&str> = self
+            .settings
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !is_known(name))
+            .collect();
+        unknown.sort_unstable();
+        match unknown.first() {
+            Some(name) => anyhow::bail!("Unknown setting '{}' in PerplexicaConfig", name),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_known(name: &str) -> bool {
+    KNOWN_SETTINGS.contains(&name)
+        || name.starts_with("PERPLEXICA_TOOL_DESC_")
+        || PROXY_VARS
+            .iter()
+            .any(|var| *var == name || var.to_ascii_lowercase() == name)
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for PerplexicaConfig {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(settings: I) -> Self {
+        let mut config = Self::default();
+        config.extend(settings);
+        config
+    }
+}
+
+/// Later values replace earlier ones, so defaults can be overlaid.
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for PerplexicaConfig {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, settings: I) {
+        for (name, value) in settings {
+            *self = std::mem::take(self).set(name, value);
+        }
+    }
+}
+
+/// Lists only the names that are set: values may be tokens.
+impl fmt::Debug for PerplexicaConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let typed = [
+            ("PERPLEXICA_API_URL", self.api_url.is_some()),
+            ("PERPLEXICA_API_TOKEN", self.api_token.is_some()),
+            ("PERPLEXICA_PROVIDER_ID", self.provider_id.is_some()),
+            ("PERPLEXICA_CHAT_MODEL_KEY", self.chat_model_key.is_some()),
+            (
+                "PERPLEXICA_EMBEDDING_MODEL_KEY",
+                self.embedding_model_key.is_some(),
+            ),
+            (
+                "PERPLEXICA_CONNECT_TIMEOUT_SECS",
+                self.connect_timeout.is_some(),
+            ),
+        ];
+        let mut names: Vec<&str> = typed
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| name)
+            .chain(self.settings.keys().map(String::as_str))
+            .collect();
+        names.sort_unstable();
+        f.debug_struct("PerplexicaConfig")
+            .field("settings", &names)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_settings_replace_earlier_ones() {
+        let mut config = PerplexicaConfig::from_iter([
+            ("PERPLEXICA_API_URL", "http://localhost:3000"),
+            ("PERPLEXICA_API_TOKEN", "secret-token"),
+        ]);
+        config.extend([("PERPLEXICA_API_URL", "https://perplexica.example.com")]);
+
+        assert_eq!(
+            config.get("PERPLEXICA_API_URL").as_deref(),
+            Some("https://perplexica.example.com")
+        );
+        assert_eq!(config.get("PERPLEXICA_PROVIDER_ID"), None);
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("PERPLEXICA_API_TOKEN"), "{}", debug);
+        assert!(!debug.contains("secret-token"), "{}", debug);
+    }
+
+    #[test]
+    fn test_builder_sets_core_settings() {
+        let config = PerplexicaConfig::default()
+            .api_url("http://localhost:3000")
+            .api_token("secret-token")
+            .provider_id("openai")
+            .chat_model_key("gpt-4o-mini")
+            .embedding_model_key("text-embedding-3-small")
+            .connect_timeout(Duration::from_secs(2))
+            .set("PERPLEXICA_MAX_RETRIES", "5");
+
+        assert_eq!(
+            config.get("PERPLEXICA_API_URL").as_deref(),
+            Some("http://localhost:3000")
+        );
+        assert_eq!(
+            config.get("PERPLEXICA_PROVIDER_ID").as_deref(),
+            Some("openai")
+        );
+        assert_eq!(
+            config.get("PERPLEXICA_CONNECT_TIMEOUT_SECS").as_deref(),
+            Some("2")
+        );
+        assert_eq!(config.get("PERPLEXICA_MAX_RETRIES").as_deref(), Some("5"));
+        assert!(config.check_names().is_ok());
+
+        // Variable names reach the same fields.
+        let config = config.set("PERPLEXICA_PROVIDER_ID", "ollama");
+        assert_eq!(config.provider_id.as_deref(), Some("ollama"));
+        // A malformed timeout is kept for validation to report.
+        let config = config.set("PERPLEXICA_CONNECT_TIMEOUT_SECS", "soon");
+        assert_eq!(
+            config.get("PERPLEXICA_CONNECT_TIMEOUT_SECS").as_deref(),
+            Some("soon")
+        );
+    }
+
+    #[test]
+    fn test_every_documented_setting_is_known() {
+        let readme = include_str!("../README.md");
+        for (start, _) in readme.match_indices("PERPLEXICA_") {
+            let name: String = readme[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || *c == '_')
+                .collect();
+            // Prefixes, not settings.
+            if !matches!(name.as_str(), "PERPLEXICA_" | "PERPLEXICA_TOOL_DESC_") {
+                assert!(is_known(&name), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_settings_are_rejected() {
+        let config = PerplexicaConfig::from_iter([
+            ("PERPLEXICA_API_URL", "http://localhost:3000"),
+            ("PERPLEXICA_TOOL_DESC_SEARCH", "Search the web"),
+            ("https_proxy", "http://proxy:3128"),
+        ]);
+        assert!(config.check_names().is_ok());
+
+        let error = config
+            .set("PERPLEXICA_MAX_RETRY", "5")
+            .check_names()
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("'PERPLEXICA_MAX_RETRY'"),
+            "{}",
+            error
+        );
+    }
+}
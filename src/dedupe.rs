@@ -0,0 +1,241 @@
+use crate::perplexica_service::PerplexicaSearchResponse;
+use std::fmt::Write;
+
+/// Query parameters dropped from URLs before comparing them: analytics tags
+/// that don't change the page.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "igshid", "ref_src",
+];
+
+/// Query parameter prefixes dropped like [`TRACKING_PARAMS`].
+const TRACKING_PARAM_PREFIXES: &[&str] = &["utm_"];
+
+/// Canonicalizes `url` for duplicate detection: the host is lowercased,
+/// tracking parameters are removed and trailing slashes are stripped from the
+/// path. Values that don't parse as URLs only lose trailing slashes.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.trim_end_matches('/').to_string();
+    };
+
+    let kept: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(name, _)| {
+            let name = name.to_ascii_lowercase();
+            !TRACKING_PARAMS.contains(&name.as_str())
+                && !TRACKING_PARAM_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(kept);
+    }
+
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+
+    // The URL parser already lowercases the host; an empty path still
+    // serializes as `/`, so that is stripped too.
+    let serialized = parsed.to_string();
+    match (parsed.query(), parsed.fragment()) {
+        (None, None) => serialized.trim_end_matches('/').to_string(),
+        _ => serialized,
+    }
+}
+
+/// Drops sources whose URL normalizes to one listed earlier, keeping the first
+/// occurrence, and renumbers the summary's `[n]` citations to match. Returns
+/// `None` when there are no duplicates.
+pub fn dedupe_sources(resp: &PerplexicaSearchResponse) -> Option<PerplexicaSearchResponse> {
+    let mut seen: Vec<String> = Vec::with_capacity(resp.sources.len());
+    // New 0-based position of each original source.
+    let mut renumbered = Vec::with_capacity(resp.sources.len());
+    let mut kept = Vec::new();
+
+    for (index, source) in resp.sources.iter().enumerate() {
+        let key = normalize_url(&source.metadata.url);
+        match seen.iter().position(|seen| *seen == key) {
+            Some(first) => renumbered.push(first),
+            None => {
+                renumbered.push(seen.len());
+                seen.push(key);
+                kept.push(index);
+            }
+        }
+    }
+
+    if kept.len() == resp.sources.len() {
+        return None;
+    }
+
+    Some(PerplexicaSearchResponse {
+        message: renumber_citations(&resp.message, &renumbered),
+        sources: kept
+            .into_iter()
+            .map(|index| resp.sources[index].clone())
+            .collect(),
+        follow_ups: resp.follow_ups.clone(),
+        extra: resp.extra.clone(),
+        interrupted: resp.interrupted.clone(),
+        served_by: resp.served_by.clone(),
+        request_id: resp.request_id.clone(),
+        cached: resp.cached,
+        stored_at: resp.stored_at.clone(),
+        settings: resp.settings.clone(),
+    })
+}
+
+/// Rewrites each `[n]` or `[n, m]` marker, mapping citation `n` to
+/// `renumbered[n - 1] + 1` and dropping numbers a marker then repeats.
+/// Numbers outside the sources, and brackets that aren't markers, are kept.
+fn renumber_citations(message: &str, renumbered: &[usize]) -> String {
+    let mut output = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(open) = rest.find('[') {
+        output.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let Some(close) = rest.find(']') else {
+            break;
+        };
+        let marker = &rest[1..close];
+        let numbers: Option<Vec<usize>> = marker
+            .split(',')
+            .map(|part| part.trim().parse().ok())
+            .collect();
+
+        match numbers {
+            Some(numbers) => {
+                let mut mapped: Vec<usize> = Vec::with_capacity(numbers.len());
+                for number in numbers {
+                    let number = match number.checked_sub(1).and_then(|i| renumbered.get(i)) {
+                        Some(position) => position + 1,
+                        None => number,
+                    };
+                    if !mapped.contains(&number) {
+                        mapped.push(number);
+                    }
+                }
+                let _ = write!(output, "[{}", mapped[0]);
+                for number in &mapped[1..] {
+                    let _ = write!(output, ", {}", number);
+                }
+                output.push(']');
+                rest = &rest[close + 1..];
+            }
+            // Not a citation marker: keep the bracket and scan on after it.
+            _ => {
+                output.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::{Source, SourceMetadata};
+
+    fn response(message: &str, sources: &[(&str, &str)]) -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: message.to_string(),
+            sources: sources
+                .iter()
+                .map(|(title, url)| Source {
+                    metadata: SourceMetadata {
+                        title: title.to_string(),
+                        url: url.to_string(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(
+            normalize_url("https://Example.COM/docs/"),
+            "https://example.com/docs"
+        );
+        assert_eq!(normalize_url("https://example.com/"), "https://example.com");
+        assert_eq!(
+            normalize_url("https://example.com/a?utm_source=x&id=7&fbclid=abc"),
+            "https://example.com/a?id=7"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/a/?UTM_Campaign=spring"),
+            "https://example.com/a"
+        );
+        assert_eq!(normalize_url("not a url/"), "not a url");
+    }
+
+    #[test]
+    fn test_exact_duplicates_are_dropped_and_citations_renumbered() {
+        let resp = response(
+            "A [1]. B [2]. C [3]. Both [1, 3].",
+            &[
+                ("First", "https://example.com/a"),
+                ("Other", "https://example.com/b"),
+                ("First again", "https://example.com/a"),
+            ],
+        );
+
+        let deduped = dedupe_sources(&resp).unwrap();
+
+        let titles: Vec<&str> = deduped
+            .sources
+            .iter()
+            .map(|s| s.metadata.title.as_str())
+            .collect();
+        assert_eq!(titles, ["First", "Other"]);
+        assert_eq!(deduped.message, "A [1]. B [2]. C [1]. Both [1].");
+    }
+
+    #[test]
+    fn test_trailing_slash_and_tracking_variants_are_duplicates() {
+        let resp = response(
+            "See [1], [2], [3] and [4]. Also [9] and [note].",
+            &[
+                ("Docs", "https://example.com/docs"),
+                ("Docs slash", "https://EXAMPLE.com/docs/"),
+                ("Blog", "https://example.com/blog"),
+                ("Docs tracked", "https://example.com/docs?utm_source=feed"),
+            ],
+        );
+
+        let deduped = dedupe_sources(&resp).unwrap();
+
+        assert_eq!(deduped.sources.len(), 2);
+        assert_eq!(deduped.sources[0].metadata.title, "Docs");
+        assert_eq!(deduped.sources[1].metadata.title, "Blog");
+        assert_eq!(
+            deduped.message,
+            "See [1], [1], [2] and [1]. Also [9] and [note]."
+        );
+    }
+
+    #[test]
+    fn test_distinct_sources_are_left_alone() {
+        let resp = response(
+            "A [1].",
+            &[
+                ("A", "https://example.com/a?id=1"),
+                ("B", "https://example.com/a?id=2"),
+            ],
+        );
+
+        assert!(dedupe_sources(&resp).is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::perplexica_service::PerplexicaSearchResponse;
+use std::collections::HashSet;
+
+/// Upper bound on how many sentences and sources from each search are compared.
+pub const MAX_DIFF_ITEMS: usize = 100;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchDiff {
+    /// Summary sentences present in A but not in B.
+    pub removed_sentences: Vec<String>,
+    /// Summary sentences present in B but not in A.
+    pub added_sentences: Vec<String>,
+    pub sources_only_in_a: Vec<String>,
+    pub sources_only_in_b: Vec<String>,
+    pub shared_sources: Vec<String>,
+    /// Whether either side was cut down to [`MAX_DIFF_ITEMS`] before comparing.
+    pub truncated: bool,
+}
+
+pub fn diff_searches(a: &PerplexicaSearchResponse, b: &PerplexicaSearchResponse) -> SearchDiff {
+    let mut truncated = false;
+    let mut bounded = |items: Vec<String>| {
+        truncated |= items.len() > MAX_DIFF_ITEMS;
+        items.into_iter().take(MAX_DIFF_ITEMS).collect::<Vec<_>>()
+    };
+
+    let sentences_a = bounded(split_sentences(&a.message));
+    let sentences_b = bounded(split_sentences(&b.message));
+    let urls_a = bounded(source_urls(a));
+    let urls_b = bounded(source_urls(b));
+
+    let (removed_sentences, added_sentences, _) = diff_lists(&sentences_a, &sentences_b);
+    let (sources_only_in_a, sources_only_in_b, shared_sources) = diff_lists(&urls_a, &urls_b);
+
+    SearchDiff {
+        removed_sentences,
+        added_sentences,
+        sources_only_in_a,
+        sources_only_in_b,
+        shared_sources,
+        truncated,
+    }
+}
+
+/// Splits text into trimmed sentences on `.`, `!` or `?` followed by whitespace.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let at_boundary = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if at_boundary {
+            let end = index + c.len_utf8();
+            sentences.push(text[start..end].trim().to_string());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim().to_string());
+
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+fn source_urls(resp: &PerplexicaSearchResponse) -> Vec<String> {
+    let mut seen = HashSet::new();
+    resp.sources
+        .iter()
+        .map(|source| source.metadata.url.clone())
+        .filter(|url| seen.insert(url.clone()))
+        .collect()
+}
+
+/// Returns `(only_in_a, only_in_b, shared)`, each in first-seen order.
+fn diff_lists(a: &[String], b: &[String]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let set_a: HashSet<&String> = a.iter().collect();
+    let set_b: HashSet<&String> = b.iter().collect();
+
+    let only_in_a = a.iter().filter(|x| !set_b.contains(x)).cloned().collect();
+    let only_in_b = b.iter().filter(|x| !set_a.contains(x)).cloned().collect();
+    let shared = a.iter().filter(|x| set_b.contains(x)).cloned().collect();
+
+    (only_in_a, only_in_b, shared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::{Source, SourceMetadata};
+
+    fn response(message: &str, urls: &[&str]) -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: message.to_string(),
+            sources: urls
+                .iter()
+                .map(|url| Source {
+                    page_content: String::new(),
+                    metadata: SourceMetadata {
+                        title: String::new(),
+                        url: url.to_string(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("Rust 1.80 is out. Is it fast? Yes!  Very"),
+            vec!["Rust 1.80 is out.", "Is it fast?", "Yes!", "Very"]
+        );
+        assert!(split_sentences("  ").is_empty());
+    }
+
+    #[test]
+    fn test_diff_sources() {
+        let a = response(
+            "",
+            &["https://a.com", "https://shared.com", "https://a.com"],
+        );
+        let b = response("", &["https://shared.com", "https://b.com"]);
+
+        let diff = diff_searches(&a, &b);
+
+        assert_eq!(diff.sources_only_in_a, vec!["https://a.com"]);
+        assert_eq!(diff.sources_only_in_b, vec!["https://b.com"]);
+        assert_eq!(diff.shared_sources, vec!["https://shared.com"]);
+        assert!(!diff.truncated);
+    }
+
+    #[test]
+    fn test_diff_summary_sentences() {
+        let a = response("Rust is fast. Rust is safe. Rust is old.", &[]);
+        let b = response("Rust is fast. Rust is safe! Rust is popular.", &[]);
+
+        let diff = diff_searches(&a, &b);
+
+        assert_eq!(
+            diff.removed_sentences,
+            vec!["Rust is safe.", "Rust is old."]
+        );
+        assert_eq!(
+            diff.added_sentences,
+            vec!["Rust is safe!", "Rust is popular."]
+        );
+    }
+
+    #[test]
+    fn test_diff_is_bounded() {
+        let long = "Sentence. ".repeat(MAX_DIFF_ITEMS + 5);
+        let a = response(&long, &[]);
+        let b = response("Other.", &[]);
+
+        let diff = diff_searches(&a, &b);
+
+        assert!(diff.truncated);
+        assert!(diff.removed_sentences.len() <= MAX_DIFF_ITEMS);
+    }
+}
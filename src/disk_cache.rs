@@ -0,0 +1,313 @@
+use crate::perplexica_service::PerplexicaSearchResponse;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Search results kept as JSON files in `PERPLEXICA_CACHE_DIR`, so identical
+/// searches can be replayed after a restart or on another machine.
+///
+/// Each file is named by a hash of the search key and holds the request that
+/// was sent, the response and when it was stored. Entries expire after a
+/// time-to-live, zero meaning never, and once the directory holds more than
+/// `max_bytes` of entries the oldest are deleted. File errors are logged and
+/// treated as misses, so the cache never fails a search.
+///
+/// The methods block on the filesystem; call them from a blocking task.
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+/// A search result read back from disk, with when it was stored.
+#[derive(Debug)]
+pub struct StoredResponse {
+    pub response: PerplexicaSearchResponse,
+    pub stored_at: SystemTime,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    /// Seconds since the Unix epoch.
+    stored_at: u64,
+    response: PerplexicaSearchResponse,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, ttl: Duration, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            ttl,
+            max_bytes,
+        }
+    }
+
+    /// Returns the result stored for `key` unless it is missing, unreadable or
+    /// expired. An expired entry is deleted.
+    pub fn get(&self, key: &str) -> Option<StoredResponse> {
+        let path = self.path(key);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(error) => {
+                tracing::warn!(path = %path.display(), error = %error, "failed to read disk cache entry");
+                return None;
+            }
+        };
+        let entry: Entry = match serde_json::from_slice(&contents) {
+            Ok(entry) => entry,
+            Err(error) => {
+                tracing::warn!(path = %path.display(), error = %error, "ignoring invalid disk cache entry");
+                return None;
+            }
+        };
+        let stored_at = UNIX_EPOCH + Duration::from_secs(entry.stored_at);
+        if self.is_expired(stored_at) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        Some(StoredResponse {
+            response: entry.response,
+            stored_at,
+        })
+    }
+
+    /// Stores `response` for `key`, with the request body that produced it,
+    /// then evicts entries past the TTL or the size limit.
+    pub fn insert(
+        &self,
+        key: &str,
+        request: &serde_json::Value,
+        response: &PerplexicaSearchResponse,
+    ) {
+        if let Err(error) = self.write(key, request, response, SystemTime::now()) {
+            tracing::warn!(dir = %self.dir.display(), error = %error, "failed to write disk cache entry");
+            return;
+        }
+        if let Err(error) = self.evict() {
+            tracing::warn!(dir = %self.dir.display(), error = %error, "failed to evict disk cache entries");
+        }
+    }
+
+    /// Writes through a temporary file, so a reader never sees half an
+    /// entry. The file's modification time is set to `stored_at`, which is
+    /// what eviction orders by.
+    fn write(
+        &self,
+        key: &str,
+        request: &serde_json::Value,
+        response: &PerplexicaSearchResponse,
+        stored_at: SystemTime,
+    ) -> std::io::Result<()> {
+        let secs = stored_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let entry = serde_json::json!({
+            "storedAt": secs,
+            "request": request,
+            "response": response,
+        });
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(&entry)?)?;
+        File::options()
+            .write(true)
+            .open(&temp)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))?;
+        fs::rename(&temp, &path)
+    }
+
+    /// Deletes expired entries, then the oldest ones until the rest fit in
+    /// `max_bytes`.
+    fn evict(&self) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let file = file?;
+            let path = file.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let metadata = file.metadata()?;
+            let modified = metadata.modified()?;
+            if self.is_expired(modified) {
+                fs::remove_file(&path)?;
+            } else {
+                entries.push((modified, metadata.len(), path));
+            }
+        }
+
+        entries.sort();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    fn is_expired(&self, stored_at: SystemTime) -> bool {
+        !self.ttl.is_zero() && stored_at.elapsed().is_ok_and(|age| age >= self.ttl)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(file_name(key))
+    }
+}
+
+/// A 64-bit FNV-1a hash of the key in hex. Unlike the standard library's
+/// hasher it is fixed, so entries stay addressable across builds and
+/// machines.
+fn file_name(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}.json", hash)
+}
+
+/// Renders a time as an RFC 3339 UTC timestamp to the second, e.g.
+/// `2026-10-15T09:30:00Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "perplexica-mcp-disk-cache-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn response(message: &str) -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ago(secs: u64) -> SystemTime {
+        SystemTime::now() - Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_round_trip_and_ttl() {
+        let dir = temp_dir("ttl");
+        let cache = DiskCache::new(dir.clone(), Duration::from_secs(60), u64::MAX);
+        let request = serde_json::json!({ "query": "What is AI?" });
+
+        assert!(cache.get("a").is_none());
+        cache.insert("a", &request, &response("fresh"));
+        let stored = cache.get("a").unwrap();
+        assert_eq!(stored.response.message, "fresh");
+        assert!(stored.stored_at.elapsed().unwrap() < Duration::from_secs(60));
+
+        let file: serde_json::Value =
+            serde_json::from_slice(&fs::read(cache.path("a")).unwrap()).unwrap();
+        assert_eq!(file["request"], request);
+
+        cache
+            .write("b", &request, &response("stale"), ago(120))
+            .unwrap();
+        assert!(cache.get("b").is_none());
+        assert!(!cache.path("b").exists());
+
+        // A zero TTL keeps entries forever.
+        let forever = DiskCache::new(dir.clone(), Duration::ZERO, u64::MAX);
+        forever
+            .write("c", &request, &response("old"), ago(10 * 365 * 86_400))
+            .unwrap();
+        assert_eq!(forever.get("c").unwrap().response.message, "old");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_eviction_drops_oldest_entries_over_the_limit() {
+        let dir = temp_dir("evict");
+        let request = serde_json::json!({});
+        let unbounded = DiskCache::new(dir.clone(), Duration::ZERO, u64::MAX);
+        for (key, age) in [("old", 300), ("middle", 200), ("new", 100)] {
+            unbounded
+                .write(key, &request, &response(key), ago(age))
+                .unwrap();
+        }
+        let entry_len = fs::metadata(unbounded.path("old")).unwrap().len();
+
+        // Room for two entries, so the oldest goes.
+        let cache = DiskCache::new(dir.clone(), Duration::ZERO, entry_len * 2 + 10);
+        cache.evict().unwrap();
+        assert!(cache.get("old").is_none());
+        assert_eq!(cache.get("middle").unwrap().response.message, "middle");
+        assert_eq!(cache.get("new").unwrap().response.message, "new");
+
+        // Expired entries go first, whatever the size.
+        let cache = DiskCache::new(dir.clone(), Duration::from_secs(150), u64::MAX);
+        cache.evict().unwrap();
+        assert!(!cache.path("middle").exists());
+        assert!(cache.path("new").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_name_is_stable() {
+        assert_eq!(file_name(""), "cbf29ce484222325.json");
+        assert_eq!(file_name("a"), "af63dc4c8601ec8c.json");
+        assert_ne!(file_name("What is AI?"), file_name("What is ML?"));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            "2000-02-29T12:34:56Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_792_056_600)),
+            "2026-10-15T09:30:00Z"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+/// Read from the working directory when `PERPLEXICA_ENV_FILE` isn't set.
+const DEFAULT_ENV_FILE: &str = ".perplexica.env";
+
+/// Loads the env file named by `PERPLEXICA_ENV_FILE`, or `.perplexica.env`
+/// in the working directory if it exists, into the process environment.
+/// Variables that are already set keep their values. Returns the file and how
+/// many variables it set, if a file was read.
+///
+/// Must run before any other thread starts, since it modifies the
+/// environment.
+pub fn load() -> anyhow::Result<Option<(PathBuf, usize)>> {
+    let path = match std::env::var("PERPLEXICA_ENV_FILE") {
+        Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+        _ if Path::new(DEFAULT_ENV_FILE).is_file() => PathBuf::from(DEFAULT_ENV_FILE),
+        _ => return Ok(None),
+    };
+
+    let contents = std::fs::read_to_string(&path).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read PERPLEXICA_ENV_FILE {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let entries = parse(&contents)
+        .map_err(|e| anyhow::anyhow!("PERPLEXICA_ENV_FILE {} {}", path.display(), e))?;
+    let unset = unset_entries(entries, |key| std::env::var_os(key).is_some());
+    for (key, value) in &unset {
+        // SAFETY: called at the start of main, before the runtime or any
+        // other thread exists, so nothing can read the environment meanwhile.
+        unsafe { std::env::set_var(key, value) };
+    }
+    Ok(Some((path, unset.len())))
+}
+
+/// The entries whose variable `is_set` doesn't report as set already, so the
+/// real environment takes precedence over the file.
+fn unset_entries(
+    entries: Vec<(String, String)>,
+    is_set: impl Fn(&str) -> bool,
+) -> Vec<(String, String)> {
+    entries
+        .into_iter()
+        .filter(|(key, _)| !is_set(key))
+        .collect()
+}
+
+/// Parses `KEY=VALUE` lines. Blank lines and lines starting with `#` are
+/// skipped, and an `export ` prefix is allowed. Values may be wrapped in
+/// single quotes, taken literally, or double quotes, where `\n`, `\"` and
+/// `\\` are unescaped; unquoted values are trimmed and end at ` #`. A later
+/// line for the same key wins.
+pub fn parse(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let error = |message: &str| format!("line {}: {}", index + 1, message);
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error("expected KEY=VALUE"));
+        };
+        let key = key.trim();
+        if !key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(error(&format!("'{}' is not a valid variable name", key)));
+        }
+        let value = parse_value(value.trim()).map_err(|message| error(&message))?;
+
+        entries.retain(|(existing, _)| existing != key);
+        entries.push((key.to_string(), value));
+    }
+    Ok(entries)
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'').ok_or("unterminated single quote")?;
+        return Ok(quoted[..end].to_string());
+    }
+
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut unescaped = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(unescaped),
+                '\\' => match chars.next() {
+                    Some('n') => unescaped.push('\n'),
+                    Some(escaped @ ('"' | '\\')) => unescaped.push(escaped),
+                    Some(other) => {
+                        unescaped.push('\\');
+                        unescaped.push(other);
+                    }
+                    None => break,
+                },
+                _ => unescaped.push(c),
+            }
+        }
+        return Err("unterminated double quote".to_string());
+    }
+
+    let value = match value.find(" #") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_blank_lines() {
+        let contents = "\
+            # Perplexica settings\n\
+            \n\
+            PERPLEXICA_API_URL=http://localhost:3000 # local instance\n\
+            export PERPLEXICA_PROVIDER_ID = openai\n\
+            PERPLEXICA_FOCUS_MODES=\n\
+            PERPLEXICA_PROVIDER_ID=ollama\n";
+
+        assert_eq!(
+            parse(contents).unwrap(),
+            [
+                entry("PERPLEXICA_API_URL", "http://localhost:3000"),
+                entry("PERPLEXICA_FOCUS_MODES", ""),
+                entry("PERPLEXICA_PROVIDER_ID", "ollama"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let contents = r#"
+PERPLEXICA_SYSTEM_INSTRUCTIONS="Cite sources # always\nBe \"brief\""
+PERPLEXICA_API_TOKEN='se#cret\n'
+PERPLEXICA_SUMMARY_HEADER=" Answer "
+"#;
+
+        assert_eq!(
+            parse(contents).unwrap(),
+            [
+                entry(
+                    "PERPLEXICA_SYSTEM_INSTRUCTIONS",
+                    "Cite sources # always\nBe \"brief\""
+                ),
+                entry("PERPLEXICA_API_TOKEN", "se#cret\\n"),
+                entry("PERPLEXICA_SUMMARY_HEADER", " Answer "),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lines() {
+        for (contents, error) in [
+            ("PERPLEXICA_API_URL", "line 1: expected KEY=VALUE"),
+            ("\n1BAD=x", "line 2: '1BAD' is not a valid variable name"),
+            ("KEY=\"open", "line 1: unterminated double quote"),
+            ("KEY='open", "line 1: unterminated single quote"),
+        ] {
+            assert_eq!(parse(contents).unwrap_err(), error, "{}", contents);
+        }
+    }
+
+    #[test]
+    fn test_existing_variables_take_precedence() {
+        let entries = vec![
+            entry("PERPLEXICA_API_URL", "http://from-file:3000"),
+            entry("PERPLEXICA_PROVIDER_ID", "openai"),
+        ];
+
+        let unset = unset_entries(entries, |key| key == "PERPLEXICA_API_URL");
+
+        assert_eq!(unset, [entry("PERPLEXICA_PROVIDER_ID", "openai")]);
+    }
+}
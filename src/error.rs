@@ -0,0 +1,520 @@
+use crate::instances::instance_label;
+use crate::retry::parse_retry_after;
+use rmcp::model::{ErrorCode, ErrorData as McpError};
+use serde_json::json;
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// Failures produced while handling a tool call.
+///
+/// Each variant converts into an [`McpError`] whose `data` is a JSON object with
+/// a machine-readable `kind` and the details a client needs to branch on, e.g.
+/// `{"kind": "upstream_status", "status": 503, "url": "..."}`.
+#[derive(Debug)]
+pub enum ServiceError {
+    /// A tool argument was rejected. `message` is the full explanation.
+    InvalidParam {
+        param: &'static str,
+        message: String,
+    },
+    /// A value was neither passed as a parameter nor configured in the environment.
+    MissingConfig {
+        field: &'static str,
+        env_var: &'static str,
+    },
+    /// The HTTP request to Perplexica could not be sent or completed.
+    Request {
+        target: &'static str,
+        url: String,
+        error: String,
+    },
+    /// Perplexica answered with a non-success status. A `429` is reported
+    /// as `rate_limited`, so throttling can be told apart from failures.
+    UpstreamStatus {
+        target: &'static str,
+        url: String,
+        status: u16,
+        body: String,
+        /// `Retry-After` and `X-RateLimit-*` headers, as
+        /// [`rate_limit_headers`] captured them.
+        headers: Vec<(String, String)>,
+    },
+    /// Perplexica answered with an empty body.
+    EmptyResponse { url: String, status: u16 },
+    /// The response body could not be read or deserialized.
+    Parse { target: &'static str, error: String },
+    /// Every concurrency permit stayed taken for the whole wait.
+    Busy { limit: usize, waited_ms: u64 },
+    /// Every concurrency permit was taken and the queue for them was full.
+    Overloaded { queue_depth: usize },
+    /// The search ran past the caller's `deadline_ms`.
+    DeadlineExceeded { deadline_ms: u64 },
+    /// The circuit breaker is open after repeated backend failures.
+    Unavailable { failures: u32, retry_in_ms: u64 },
+    /// The server was started with `PERPLEXICA_SEARCH_DISABLED`.
+    SearchDisabled,
+    /// The client cancelled the request before it finished.
+    Cancelled,
+    /// Shutdown has begun, so no new search is started.
+    ShuttingDown,
+    /// No resource exists at the requested URI.
+    ResourceNotFound { uri: String },
+    /// Anything else that went wrong inside the server.
+    Internal(String),
+}
+
+impl ServiceError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidParam { .. } => "invalid_param",
+            Self::MissingConfig { .. } => "missing_config",
+            Self::Request { .. } => "request_failed",
+            Self::UpstreamStatus { status: 429, .. } => "rate_limited",
+            Self::UpstreamStatus { .. } => "upstream_status",
+            Self::EmptyResponse { .. } => "empty_response",
+            Self::Parse { .. } => "parse_error",
+            Self::Busy { .. } => "server_busy",
+            Self::Overloaded { .. } => "server_overloaded",
+            Self::DeadlineExceeded { .. } => "deadline_exceeded",
+            Self::Unavailable { .. } => "backend_unavailable",
+            Self::SearchDisabled => "search_disabled",
+            Self::Cancelled => "cancelled",
+            Self::ShuttingDown => "shutting_down",
+            Self::ResourceNotFound { .. } => "resource_not_found",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// Whether sending the same request again might succeed: connection
+    /// failures, timeouts, rate limiting and server errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request { .. } => true,
+            Self::UpstreamStatus { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// The wait a retryable upstream response asked for in `Retry-After`.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::UpstreamStatus { headers, .. } if self.is_retryable() => headers
+                .iter()
+                .find(|(name, _)| name == "retry-after")
+                .and_then(|(_, value)| parse_retry_after(value)),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidParam { .. } | Self::MissingConfig { .. } => ErrorCode(-32602),
+            Self::ResourceNotFound { .. } => ErrorCode::RESOURCE_NOT_FOUND,
+            _ => ErrorCode(-32603),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::InvalidParam { message, .. } => message.clone(),
+            Self::MissingConfig { field, env_var } => format!(
+                "Missing {}. Set either the {} parameter or {} environment variable",
+                field, field, env_var
+            ),
+            Self::Request { target, error, .. } => {
+                format!("{} request failed: {}", capitalize(target), error)
+            }
+            Self::UpstreamStatus {
+                target,
+                status: 429,
+                body,
+                ..
+            } => {
+                let retry = match self.retry_after() {
+                    Some(wait) => format!(" Try again in {:.1}s.", wait.as_secs_f64()),
+                    None => " Try again later.".to_string(),
+                };
+                format!(
+                    "Rate limited by Perplexica ({} API, status {}).{} {}",
+                    target,
+                    status_line(429),
+                    retry,
+                    body
+                )
+                .trim_end()
+                .to_string()
+            }
+            Self::UpstreamStatus {
+                target,
+                status,
+                body,
+                ..
+            } => format!(
+                "Perplexica {} API error (status {}): {}",
+                target,
+                status_line(*status),
+                body
+            ),
+            Self::EmptyResponse { status, .. } => format!(
+                "Perplexica returned an empty response (status {}). Check that the Perplexica backend is running and the selected model is available.",
+                status
+            ),
+            Self::Parse { target, error } => {
+                format!("Failed to parse {} response as JSON: {}", target, error)
+            }
+            Self::Busy { limit, waited_ms } => format!(
+                "Server busy: all {} concurrent search slots stayed in use for {} ms. Try again shortly.",
+                limit, waited_ms
+            ),
+            Self::Overloaded { queue_depth } => format!(
+                "Server overloaded: {} searches are already queued for a search slot. Try again shortly.",
+                queue_depth
+            ),
+            Self::DeadlineExceeded { deadline_ms } => format!(
+                "Deadline exceeded: the search did not finish within the requested {} ms",
+                deadline_ms
+            ),
+            Self::Unavailable {
+                failures,
+                retry_in_ms,
+            } => format!(
+                "Perplexica backend unavailable: {} consecutive requests failed, so requests are paused. Try again in {:.1}s.",
+                failures,
+                *retry_in_ms as f64 / 1000.0
+            ),
+            Self::SearchDisabled => "Searches are disabled on this server (PERPLEXICA_SEARCH_DISABLED is set). Provider discovery tools such as perplexica_providers still work.".to_string(),
+            Self::Cancelled => "Search cancelled by the client".to_string(),
+            Self::ShuttingDown => "Server is shutting down and not starting new searches. Try again once it has restarted.".to_string(),
+            Self::ResourceNotFound { uri } => format!("Resource not found: {}", uri),
+            Self::Internal(message) => message.clone(),
+        }
+    }
+
+    fn data(&self) -> serde_json::Value {
+        let kind = self.kind();
+        match self {
+            Self::InvalidParam { param, .. } => json!({ "kind": kind, "param": param }),
+            Self::MissingConfig { field, env_var } => {
+                json!({ "kind": kind, "field": field, "env_var": env_var })
+            }
+            Self::Request { url, .. } => json!({ "kind": kind, "url": without_credentials(url) }),
+            Self::UpstreamStatus {
+                url,
+                status,
+                headers,
+                ..
+            } => {
+                let mut data = json!({
+                    "kind": kind,
+                    "status": status,
+                    "url": without_credentials(url),
+                });
+                if !headers.is_empty() {
+                    data["headers"] = headers
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone().into()))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into();
+                }
+                if let Some(retry_after) = self.retry_after() {
+                    data["retry_after_ms"] = (retry_after.as_millis() as u64).into();
+                }
+                data
+            }
+            Self::EmptyResponse { url, status } => {
+                json!({ "kind": kind, "status": status, "url": without_credentials(url) })
+            }
+            Self::Parse { target, .. } => json!({ "kind": kind, "target": target }),
+            Self::Busy { limit, waited_ms } => {
+                json!({ "kind": kind, "limit": limit, "waited_ms": waited_ms })
+            }
+            Self::Overloaded { queue_depth } => {
+                json!({ "kind": kind, "queue_depth": queue_depth })
+            }
+            Self::DeadlineExceeded { deadline_ms } => {
+                json!({ "kind": kind, "deadline_ms": deadline_ms })
+            }
+            Self::Unavailable { retry_in_ms, .. } => {
+                json!({ "kind": kind, "retry_after_ms": retry_in_ms })
+            }
+            Self::ResourceNotFound { uri } => json!({ "kind": kind, "uri": uri }),
+            Self::SearchDisabled | Self::Cancelled | Self::ShuttingDown | Self::Internal(_) => {
+                json!({ "kind": kind })
+            }
+        }
+    }
+}
+
+impl From<ServiceError> for McpError {
+    fn from(error: ServiceError) -> Self {
+        McpError {
+            code: error.code(),
+            message: Cow::from(error.message()),
+            data: Some(error.data()),
+        }
+    }
+}
+
+/// The `kind` in a tool error's data, or `"unknown"` for errors that didn't
+/// come from a [`ServiceError`].
+pub fn error_kind(error: &McpError) -> &str {
+    error
+        .data
+        .as_ref()
+        .and_then(|data| data["kind"].as_str())
+        .unwrap_or("unknown")
+}
+
+/// Replaces every occurrence of each non-empty secret in `text` with
+/// `[redacted]`. The JSON-escaped form is replaced too, since upstream error
+/// bodies often echo the request JSON.
+pub fn redact(text: &str, secrets: &[&str]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets.iter().filter(|secret| !secret.trim().is_empty()) {
+        text = text.replace(secret, "[redacted]");
+        // Serializing a string cannot fail; strip the surrounding quotes.
+        let escaped = serde_json::to_string(secret).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+        if escaped != *secret {
+            text = text.replace(escaped, "[redacted]");
+        }
+    }
+    text
+}
+
+/// `url` without any `user:password@`, since `PERPLEXICA_API_URL` may embed
+/// credentials that must not reach clients.
+fn without_credentials(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => instance_label(&parsed),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Replaces an error body whose `Content-Type` says it isn't JSON, typically a
+/// reverse proxy's HTML error page, with a one-line description of it. Bodies
+/// without a `Content-Type` are kept, since they can't be told apart.
+pub fn summarize_non_json_body(content_type: Option<&str>, body: String, status: u16) -> String {
+    let Some(content_type) = content_type else {
+        return body;
+    };
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if media_type == "application/json" || media_type.ends_with("+json") {
+        return body;
+    }
+    format!(
+        "upstream returned {} ({} bytes), status {}",
+        media_type,
+        body.len(),
+        status
+    )
+}
+
+/// Picks the rate limiting headers worth passing on from an error response:
+/// `Retry-After` and any `X-RateLimit-*`, with lowercase names. Values that
+/// aren't valid text are skipped.
+pub fn rate_limit_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            *name == reqwest::header::RETRY_AFTER || name.as_str().starts_with("x-ratelimit-")
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Appended by [`truncate_body`] where it cuts.
+pub const TRUNCATION_MARKER: &str = "… [truncated]";
+
+/// Cuts `body` down to `max_chars` characters, marking the cut.
+pub fn truncate_body(body: String, max_chars: usize) -> String {
+    match body.char_indices().nth(max_chars) {
+        Some((end, _)) => format!(
+            "{}{}",
+            truncate_on_char_boundary(&body, end),
+            TRUNCATION_MARKER
+        ),
+        None => body,
+    }
+}
+
+/// The longest prefix of `s` that is at most `max_bytes` long and doesn't
+/// split a character. Every truncation goes through this, so a limit that
+/// lands inside a multibyte character backs off to its start instead of
+/// panicking. Callers append their own marker.
+pub fn truncate_on_char_boundary(s: &str, max_bytes: usize) -> &str {
+    if max_bytes >= s.len() {
+        return s;
+    }
+    // A character is at most 4 bytes, so this steps back at most 3 times.
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&end| s.is_char_boundary(end))
+        .unwrap_or(0);
+    &s[..end]
+}
+
+/// Renders a status as reqwest does, e.g. `503 Service Unavailable`.
+fn status_line(status: u16) -> String {
+    match reqwest::StatusCode::from_u16(status) {
+        Ok(status) => status.to_string(),
+        Err(_) => status.to_string(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_status_conversion() {
+        let error: McpError = ServiceError::UpstreamStatus {
+            target: "search",
+            url: "http://localhost:3000/api/search".to_string(),
+            status: 503,
+            body: "down".to_string(),
+            headers: Vec::new(),
+        }
+        .into();
+
+        assert_eq!(error.code, ErrorCode(-32603));
+        assert_eq!(
+            error.message,
+            "Perplexica search API error (status 503 Service Unavailable): down"
+        );
+        assert_eq!(
+            error.data,
+            Some(json!({
+                "kind": "upstream_status",
+                "status": 503,
+                "url": "http://localhost:3000/api/search"
+            }))
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_headers_in_data() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "5".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+
+        let error = ServiceError::UpstreamStatus {
+            target: "search",
+            url: "http://localhost:3000/api/search".to_string(),
+            status: 429,
+            body: "slow down".to_string(),
+            headers: rate_limit_headers(&headers),
+        };
+
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+        let error: McpError = error.into();
+        assert_eq!(
+            error.message,
+            "Rate limited by Perplexica (search API, status 429 Too Many Requests). Try again in 5.0s. slow down"
+        );
+        let data = error.data.unwrap();
+        assert_eq!(data["kind"], "rate_limited");
+        assert_eq!(
+            data["headers"],
+            json!({ "retry-after": "5", "x-ratelimit-remaining": "0" })
+        );
+        assert_eq!(data["retry_after_ms"], 5000);
+    }
+
+    #[test]
+    fn test_request_failure_message() {
+        let error: McpError = ServiceError::Request {
+            target: "providers",
+            url: "http://localhost:3000/api/providers".to_string(),
+            error: "connection refused".to_string(),
+        }
+        .into();
+
+        assert_eq!(
+            error.message,
+            "Providers request failed: connection refused"
+        );
+        assert_eq!(error.data.unwrap()["kind"], "request_failed");
+    }
+
+    #[test]
+    fn test_redact_replaces_raw_and_json_escaped_secrets() {
+        let body = r#"{"error":"bad query","query":"my \"secret\" plan","raw":"my "secret" plan"}"#;
+
+        let redacted = redact(body, &["my \"secret\" plan", ""]);
+
+        assert_eq!(
+            redacted,
+            r#"{"error":"bad query","query":"[redacted]","raw":"[redacted]"}"#
+        );
+    }
+
+    #[test]
+    fn test_summarize_non_json_body() {
+        let html = "<html><body><h1>504 Gateway Time-out</h1></body></html>".to_string();
+
+        assert_eq!(
+            summarize_non_json_body(Some("text/html; charset=utf-8"), html.clone(), 504),
+            format!(
+                "upstream returned text/html ({} bytes), status 504",
+                html.len()
+            )
+        );
+        for content_type in [
+            Some("application/json"),
+            Some("application/problem+json; charset=utf-8"),
+            None,
+        ] {
+            assert_eq!(
+                summarize_non_json_body(content_type, "{}".to_string(), 500),
+                "{}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        assert_eq!(truncate_body("héllo".to_string(), 2), "hé… [truncated]");
+        assert_eq!(truncate_body("hello".to_string(), 5), "hello");
+        assert_eq!(
+            truncate_body("ok 👍🏽 done".to_string(), 4),
+            "ok 👍… [truncated]"
+        );
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary_backs_off_multibyte_characters() {
+        // "é" is 2 bytes and "👍" 4, so these limits land inside them.
+        assert_eq!(truncate_on_char_boundary("café", 4), "caf");
+        assert_eq!(truncate_on_char_boundary("café", 5), "café");
+        assert_eq!(truncate_on_char_boundary("naïve", 3), "na");
+        for max_bytes in 1..4 {
+            assert_eq!(truncate_on_char_boundary("👍 ok", max_bytes), "");
+        }
+        assert_eq!(truncate_on_char_boundary("👍 ok", 4), "👍");
+        assert_eq!(truncate_on_char_boundary("ok", 0), "");
+        assert_eq!(truncate_on_char_boundary("ok", 100), "ok");
+
+        // No limit panics, whatever character it lands in.
+        let text = "Zürich 👩‍👩‍👧 日本語 ñ";
+        for max_bytes in 0..=text.len() + 1 {
+            let prefix = truncate_on_char_boundary(text, max_bytes);
+            assert!(text.starts_with(prefix));
+            assert!(prefix.len() <= max_bytes);
+        }
+    }
+}
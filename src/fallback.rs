@@ -0,0 +1,145 @@
+use crate::error::error_kind;
+use rmcp::ErrorData as McpError;
+
+/// A provider and models to search with when the requested ones fail, from
+/// `PERPLEXICA_FALLBACK_PROVIDERS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackProvider {
+    pub provider_id: String,
+    pub chat_model_key: String,
+    pub embedding_model_key: String,
+}
+
+impl FallbackProvider {
+    /// How the fallback is named in notes and logs.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (chat model {}, embedding model {})",
+            self.provider_id, self.chat_model_key, self.embedding_model_key
+        )
+    }
+}
+
+/// Parses a comma-separated list of `provider_id:chat_model_key:embedding_model_key`
+/// triples, tried in the order given.
+pub fn parse_fallback_providers(value: &str) -> Result<Vec<FallbackProvider>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+            match parts[..] {
+                [provider_id, chat_model_key, embedding_model_key]
+                    if parts.iter().all(|part| !part.is_empty()) =>
+                {
+                    Ok(FallbackProvider {
+                        provider_id: provider_id.to_string(),
+                        chat_model_key: chat_model_key.to_string(),
+                        embedding_model_key: embedding_model_key.to_string(),
+                    })
+                }
+                _ => Err(format!(
+                    "PERPLEXICA_FALLBACK_PROVIDERS entry '{}' must be provider_id:chat_model_key:embedding_model_key; use a model alias for keys containing ':'",
+                    entry
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Whether a failed search may succeed with another provider or model: the
+/// backend failed (5xx) or answered empty, or rejected the request (4xx)
+/// over its provider or model. Anything else, such as an invalid parameter,
+/// an unreachable backend or a busy server, would fail the same way again.
+pub fn is_provider_error(error: &McpError) -> bool {
+    let status = error
+        .data
+        .as_ref()
+        .and_then(|data| data["status"].as_u64())
+        .unwrap_or_default();
+    match error_kind(error) {
+        "empty_response" => true,
+        "upstream_status" if status >= 500 => true,
+        "upstream_status" if (400..500).contains(&status) => {
+            let message = error.message.to_lowercase();
+            message.contains("model") || message.contains("provider")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ServiceError;
+
+    #[test]
+    fn test_parse_fallback_providers() {
+        assert_eq!(
+            parse_fallback_providers(
+                " ollama:llama3:nomic-embed-text , openai:gpt-4o-mini:text-embedding-3-small,"
+            )
+            .unwrap(),
+            [
+                FallbackProvider {
+                    provider_id: "ollama".to_string(),
+                    chat_model_key: "llama3".to_string(),
+                    embedding_model_key: "nomic-embed-text".to_string(),
+                },
+                FallbackProvider {
+                    provider_id: "openai".to_string(),
+                    chat_model_key: "gpt-4o-mini".to_string(),
+                    embedding_model_key: "text-embedding-3-small".to_string(),
+                },
+            ]
+        );
+
+        for invalid in ["ollama:llama3", "ollama::nomic", "ollama:llama3:8b:nomic"] {
+            let error = parse_fallback_providers(invalid).unwrap_err();
+            assert!(error.contains(invalid), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_only_provider_and_model_errors_fall_back() {
+        let upstream = |status, body: &str| {
+            McpError::from(ServiceError::UpstreamStatus {
+                target: "search",
+                url: "http://localhost:3000/api/search".to_string(),
+                status,
+                body: body.to_string(),
+                headers: Vec::new(),
+            })
+        };
+
+        assert!(is_provider_error(&upstream(500, "provider unavailable")));
+        assert!(is_provider_error(&upstream(
+            400,
+            r#"{"message":"Invalid model selected"}"#
+        )));
+        assert!(is_provider_error(&McpError::from(
+            ServiceError::EmptyResponse {
+                url: String::new(),
+                status: 200,
+            }
+        )));
+
+        assert!(!is_provider_error(&upstream(
+            400,
+            r#"{"message":"Missing query"}"#
+        )));
+        assert!(!is_provider_error(&upstream(429, "model rate limited")));
+        assert!(!is_provider_error(&McpError::from(
+            ServiceError::InvalidParam {
+                param: "query",
+                message: "query must not be empty".to_string(),
+            }
+        )));
+        assert!(!is_provider_error(&McpError::from(ServiceError::Request {
+            target: "search",
+            url: String::new(),
+            error: "connection refused".to_string(),
+        })));
+    }
+}
@@ -0,0 +1,2506 @@
+use crate::diff::{MAX_DIFF_ITEMS, SearchDiff};
+use crate::error::{TRUNCATION_MARKER, truncate_body, truncate_on_char_boundary};
+use crate::loadtest::LoadtestReport;
+use crate::perplexica_service::{
+    Model, PerplexicaSearchResponse, ProvidersResponse, SearchSettings, SourceMetadata,
+};
+use crate::ping::Check;
+use crate::pricing::{
+    ASSUMED_CONTEXT_TOKENS, ASSUMED_OUTPUT_TOKENS, CHARS_PER_TOKEN, CostEstimate, ModelCost,
+};
+use crate::ranking::{count_citations, most_cited_source, sort_by_score};
+use crate::stats::StatsSnapshot;
+use crate::suggest::ModelSuggestion;
+use crate::verify::Reachability;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Markdown,
+    Json,
+    /// Everything Perplexica returned, including fields `Json` leaves out.
+    FullJson,
+    ChatMl,
+    /// The summary and a sources list without markdown syntax, for clients
+    /// that don't render markdown.
+    Text,
+}
+
+impl OutputFormat {
+    pub const VALID_VALUES: &'static [&'static str] =
+        &["markdown", "json", "full_json", "chatml", "text"];
+
+    /// The `output_format` value that selects this format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Json => "json",
+            Self::FullJson => "full_json",
+            Self::ChatMl => "chatml",
+            Self::Text => "text",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "full_json" => Ok(Self::FullJson),
+            "chatml" => Ok(Self::ChatMl),
+            "text" => Ok(Self::Text),
+            other => Err(format!(
+                "Invalid output_format '{}'. Valid values are: {}",
+                other,
+                Self::VALID_VALUES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Upper bound on the buffer reserved up front for markdown output.
+const MAX_RESERVED_CAPACITY: usize = 1 << 20;
+
+pub const DEFAULT_SUMMARY_HEADING: &str = "Summary";
+pub const DEFAULT_SOURCES_HEADING: &str = "Sources";
+
+/// Appended to markdown when a search found no sources, with
+/// [`FormatOptions::no_sources_hint`].
+pub const NO_SOURCES_HINT: &str = "No sources were found — consider rephrasing or trying a different focus mode (e.g. academicSearch).";
+
+/// Rendering knobs for [`format_search_result`].
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Order in which to list sources, as indices into `sources`. Each source
+    /// keeps its original citation number wherever it ends up in the list.
+    pub source_order: Option<Vec<usize>>,
+    /// Maximum number of sources to list; the rest are summarized in a note.
+    pub max_sources: Option<usize>,
+    /// When set, each source's `pageContent` is shown, trimmed to this many characters.
+    pub page_content_chars: Option<usize>,
+    /// Separate sources the summary cites with `[n]` from those it doesn't.
+    pub mark_cited: bool,
+    /// Show each source's relevance score, and list sources by descending
+    /// score unless `source_order` is set.
+    pub include_scores: bool,
+    /// Leave the sources out entirely, so markdown and text show only the
+    /// answer.
+    pub omit_sources: bool,
+    /// Markdown suggests rephrasing the query when there are no sources to
+    /// list.
+    pub no_sources_hint: bool,
+    /// Markdown lists the response's follow-up questions, if it has any,
+    /// after the sources.
+    pub follow_ups: bool,
+    /// Markdown ends with a fenced `json` block listing every source as
+    /// `{title, url}`, for tools that parse the sources.
+    pub sources_json: bool,
+    /// What each source's URL answered, by source index; markdown shows it
+    /// next to the source.
+    pub source_status: Option<Vec<Reachability>>,
+    /// Markdown shows longer URLs as a shortened link to the full URL.
+    pub max_url_chars: Option<usize>,
+    /// How long the search took; markdown ends with a timing footer when set.
+    pub elapsed: Option<Duration>,
+    pub headings: Headings,
+}
+
+/// Titles of the summary and sources sections in markdown output, from
+/// `PERPLEXICA_SUMMARY_HEADER` and `PERPLEXICA_SOURCES_HEADER`. The heading
+/// level still depends on where the result is rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Headings {
+    pub summary: String,
+    pub sources: String,
+}
+
+impl Default for Headings {
+    fn default() -> Self {
+        Self {
+            summary: DEFAULT_SUMMARY_HEADING.to_string(),
+            sources: DEFAULT_SOURCES_HEADING.to_string(),
+        }
+    }
+}
+
+/// Renders a search result for `query`. Only the ChatML format includes the
+/// query itself.
+pub fn format_search_result(
+    query: &str,
+    resp: &PerplexicaSearchResponse,
+    format: OutputFormat,
+    options: &FormatOptions,
+) -> String {
+    // Serializing plain strings and vectors cannot fail.
+    match format {
+        OutputFormat::Markdown => format_markdown(resp, options, 2),
+        OutputFormat::Json => serde_json::to_string_pretty(&json_view(resp)).unwrap_or_default(),
+        OutputFormat::FullJson => serde_json::to_string_pretty(resp).unwrap_or_default(),
+        OutputFormat::ChatMl => {
+            serde_json::to_string_pretty(&chatml_transcript(query, resp, options))
+                .unwrap_or_default()
+        }
+        OutputFormat::Text => format_text(resp, options),
+    }
+}
+
+/// Renders like [`format_search_result`], but cuts the summary short when the
+/// output would run past `max_chars` characters. Sources are always kept, so
+/// the citations left in the summary still resolve. Returns whether the
+/// summary was cut.
+pub fn format_search_result_capped(
+    query: &str,
+    resp: &PerplexicaSearchResponse,
+    format: OutputFormat,
+    options: &FormatOptions,
+    max_chars: Option<usize>,
+) -> (String, bool) {
+    let output = format_search_result(query, resp, format, options);
+    let excess = match max_chars {
+        Some(max_chars) => output.chars().count().saturating_sub(max_chars),
+        None => 0,
+    };
+    if excess == 0 {
+        return (output, false);
+    }
+
+    // Escaping only makes the rendered summary longer than the raw one, so
+    // dropping `excess` raw characters is enough for every format.
+    let keep = resp
+        .message
+        .chars()
+        .count()
+        .saturating_sub(excess + TRUNCATION_MARKER.chars().count());
+    let truncated = PerplexicaSearchResponse {
+        message: truncate_body(resp.message.clone(), keep),
+        ..resp.clone()
+    };
+    (
+        format_search_result(query, &truncated, format, options),
+        true,
+    )
+}
+
+/// The response without the unrecognized fields that `full_json` also
+/// carries. Removing exactly the `extra` keys keeps this in step with the
+/// serde attributes on the response types.
+fn json_view(resp: &PerplexicaSearchResponse) -> serde_json::Value {
+    let mut view = serde_json::to_value(resp).unwrap_or_default();
+    let remove = |object: &mut serde_json::Value, extra: &serde_json::Map<_, _>| {
+        if let Some(object) = object.as_object_mut() {
+            for key in extra.keys() {
+                object.remove(key);
+            }
+        }
+    };
+
+    remove(&mut view, &resp.extra);
+    if let Some(sources) = view["sources"].as_array_mut() {
+        for (view, source) in sources.iter_mut().zip(&resp.sources) {
+            remove(view, &source.extra);
+            remove(&mut view["metadata"], &source.metadata.extra);
+        }
+    }
+    view
+}
+
+/// Collects notices about what was left out or ignored while rendering, so
+/// clients can surface them without parsing the output. The markdown output
+/// keeps its own inline notes as well.
+pub fn search_warnings(
+    resp: &PerplexicaSearchResponse,
+    format: OutputFormat,
+    options: &FormatOptions,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Some(reason) = &resp.interrupted {
+        warnings.push(interrupted_note(reason));
+    }
+    if let Some(fallback) = &resp.served_by {
+        warnings.push(fallback_note(fallback));
+    }
+
+    let json = matches!(format, OutputFormat::Json | OutputFormat::FullJson);
+    let text = format == OutputFormat::Text;
+    let ignored: Vec<&str> = [
+        ("rank_sources", json && options.source_order.is_some()),
+        ("max_sources", json && options.max_sources.is_some()),
+        (
+            "include_page_content",
+            (json || text) && options.page_content_chars.is_some(),
+        ),
+        ("mark_cited", (json || text) && options.mark_cited),
+    ]
+    .into_iter()
+    .filter_map(|(param, set)| set.then_some(param))
+    .collect();
+    if !ignored.is_empty() {
+        warnings.push(format!(
+            "{} ignored by the {} output format",
+            ignored.join(", "),
+            format.name()
+        ));
+    }
+    if json {
+        return warnings;
+    }
+
+    let (order, total) = listed_sources(resp, options);
+    if order.len() < total {
+        warnings.push(format!(
+            "{} of {} sources omitted by max_sources",
+            total - order.len(),
+            total
+        ));
+    }
+
+    if let Some(max_chars) = options.page_content_chars.filter(|_| !text) {
+        let trimmed = order
+            .iter()
+            .filter(|&&index| {
+                collapse_whitespace(&resp.sources[index].page_content)
+                    .chars()
+                    .count()
+                    > max_chars
+            })
+            .count();
+        if trimmed > 0 {
+            warnings.push(format!(
+                "Page content trimmed to {} characters for {} {}",
+                max_chars,
+                trimmed,
+                if trimmed == 1 { "source" } else { "sources" }
+            ));
+        }
+    }
+
+    warnings
+}
+
+fn cached_note(resp: &PerplexicaSearchResponse) -> String {
+    match &resp.stored_at {
+        Some(stored_at) => format!("Served from disk cache, stored {}", stored_at),
+        None => "Served from cache".to_string(),
+    }
+}
+
+fn fallback_note(fallback: &str) -> String {
+    format!(
+        "Served by fallback provider {} after the requested provider failed",
+        fallback
+    )
+}
+
+fn interrupted_note(reason: &str) -> String {
+    format!(
+        "Response truncated: the stream from Perplexica was interrupted ({}), so the answer above is partial",
+        reason
+    )
+}
+
+/// Returns the source indices to list, in display order, and the total number
+/// of sources before `max_sources` was applied.
+fn listed_sources(resp: &PerplexicaSearchResponse, options: &FormatOptions) -> (Vec<usize>, usize) {
+    let mut order: Vec<usize> = match &options.source_order {
+        Some(order) => order.clone(),
+        None if options.include_scores => sort_by_score(&resp.sources),
+        None => (0..resp.sources.len()).collect(),
+    };
+    order.retain(|&index| index < resp.sources.len());
+    let total = order.len();
+    if let Some(max_sources) = options.max_sources {
+        order.truncate(max_sources);
+    }
+    (order, total)
+}
+
+/// Renders the summary and sources with headings at `level` (2 for `##`), so
+/// the result can be nested inside a larger report.
+fn format_markdown(
+    resp: &PerplexicaSearchResponse,
+    options: &FormatOptions,
+    level: usize,
+) -> String {
+    let heading = "#".repeat(level);
+    let mut markdown = String::with_capacity(markdown_capacity(resp, options, level));
+
+    // Writing into a String is infallible, so the fmt::Result is ignored.
+    let _ = write!(
+        markdown,
+        "{heading} {}\n\n{}\n\n",
+        options.headings.summary, resp.message
+    );
+    if let Some(reason) = &resp.interrupted {
+        let _ = write!(markdown, "_{}_\n\n", interrupted_note(reason));
+    }
+    if let Some(fallback) = &resp.served_by {
+        let _ = write!(markdown, "_{}_\n\n", fallback_note(fallback));
+    }
+    if options.omit_sources {
+        // Footers follow a single line break, as they would the sources.
+        markdown.truncate(markdown.trim_end().len());
+        markdown.push('\n');
+    } else {
+        let _ = writeln!(markdown, "{heading} {}\n", options.headings.sources);
+        if resp.sources.is_empty() {
+            markdown.push_str("No sources found.\n");
+            if options.no_sources_hint {
+                let _ = write!(markdown, "\n_{}_\n", NO_SOURCES_HINT);
+            }
+        } else {
+            let (order, total) = listed_sources(resp, options);
+            // Media results get their own section; keeping their citation
+            // numbers means the summary's markers still line up.
+            let (media, order): (Vec<usize>, Vec<usize>) = order
+                .iter()
+                .partition(|&&index| resp.sources[index].metadata.is_media());
+
+            if order.is_empty() {
+                markdown.push_str("No web sources found.\n");
+            } else if options.mark_cited {
+                let cited = cited_sources(resp);
+                let (listed, uncited): (Vec<usize>, Vec<usize>) =
+                    order.iter().partition(|&&index| cited[index]);
+
+                if listed.is_empty() {
+                    markdown.push_str("The summary does not cite any of these sources.\n");
+                }
+                write_sources(&mut markdown, resp, &listed, options);
+                if !uncited.is_empty() {
+                    let _ = write!(markdown, "\n{heading}# Additional sources\n\n");
+                    write_sources(&mut markdown, resp, &uncited, options);
+                }
+            } else {
+                write_sources(&mut markdown, resp, &order, options);
+            }
+
+            if !media.is_empty() {
+                let _ = write!(markdown, "\n{heading} Media\n\n");
+                write_media(&mut markdown, resp, &media, options);
+            }
+
+            let listed = order.len() + media.len();
+            if listed < total {
+                let _ = writeln!(markdown, "\n(showing {} of {} sources)", listed, total);
+            }
+        }
+    }
+
+    if let Some(questions) = displayed_follow_ups(resp, options) {
+        let _ = write!(markdown, "\n{heading} Follow-up Questions\n\n");
+        for question in questions {
+            let _ = writeln!(markdown, "- {}", question);
+        }
+    }
+
+    if let Some(elapsed) = options.elapsed {
+        let _ = writeln!(
+            markdown,
+            "\n_Search completed in {:.1}s_",
+            elapsed.as_secs_f64()
+        );
+    }
+    if resp.cached {
+        let _ = writeln!(markdown, "\n_{}_", cached_note(resp));
+    }
+    if let Some(request_id) = &resp.request_id {
+        let _ = writeln!(markdown, "\n_Request ID: {}_", request_id);
+    }
+    if options.sources_json {
+        let _ = write!(markdown, "\n```json\n{}\n```\n", sources_json(resp));
+    }
+
+    markdown
+}
+
+/// Room for [`format_markdown`]'s output, so it is built without
+/// reallocating. Each part is sized for the longest form it can render in,
+/// so this is an upper bound. Only a hint, though: saturating and capped, so
+/// a huge response can't overflow the estimate or reserve an outsized buffer
+/// up front.
+fn markdown_capacity(
+    resp: &PerplexicaSearchResponse,
+    options: &FormatOptions,
+    level: usize,
+) -> usize {
+    // Headings, blank lines and the fixed notes, such as "(showing n of m
+    // sources)" or a subsection heading.
+    const FIXED: usize = 256;
+    // `N. ` before the title and ` — ` plus a newline after it.
+    const LINE: usize = 32;
+
+    let headings = level
+        .saturating_add(options.headings.summary.len())
+        .saturating_add(level + 1)
+        .saturating_add(options.headings.sources.len());
+    let notes = [
+        resp.interrupted.as_deref().map(interrupted_note),
+        resp.served_by.as_deref().map(fallback_note),
+    ]
+    .iter()
+    .flatten()
+    .map(|note| note.len() + 4)
+    .fold(0, usize::saturating_add);
+    let footers = [
+        options.elapsed.map(|_| 40),
+        resp.cached.then(|| cached_note(resp).len() + 4),
+        resp.request_id.as_ref().map(|id| id.len() + 24),
+        (options.no_sources_hint && resp.sources.is_empty()).then_some(NO_SOURCES_HINT.len() + 4),
+        options.sources_json.then(|| sources_json_capacity(resp)),
+        displayed_follow_ups(resp, options).map(|questions| {
+            questions
+                .map(|question| question.len() + 3)
+                .fold(32, usize::saturating_add)
+        }),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(0, usize::saturating_add);
+
+    resp.sources
+        .iter()
+        .map(|source| source_capacity(&source.metadata, &source.page_content, options) + LINE)
+        .fold(resp.message.len(), usize::saturating_add)
+        .saturating_add(FIXED)
+        .saturating_add(headings)
+        .saturating_add(notes)
+        .saturating_add(footers)
+        .min(MAX_RESERVED_CAPACITY)
+}
+
+/// The follow-up questions markdown lists: the non-blank ones, trimmed, when
+/// they are enabled and there are any.
+fn displayed_follow_ups<'a>(
+    resp: &'a PerplexicaSearchResponse,
+    options: &FormatOptions,
+) -> Option<impl Iterator<Item = &'a str>> {
+    let questions = resp.follow_ups.as_deref().filter(|_| options.follow_ups)?;
+    let mut questions = questions
+        .iter()
+        .map(|question| question.trim())
+        .filter(|question| !question.is_empty())
+        .peekable();
+    questions.peek()?;
+    Some(questions)
+}
+
+/// Room for one listed source beyond its numbering and separators.
+fn source_capacity(
+    metadata: &SourceMetadata,
+    page_content: &str,
+    options: &FormatOptions,
+) -> usize {
+    let len = |field: &Option<String>| field.as_ref().map_or(0, String::len);
+    // A shortened URL repeats it as the link target, with spaces and
+    // parentheses percent-encoded.
+    let url = match options.max_url_chars {
+        Some(_) => metadata.url.len().saturating_mul(4),
+        None => metadata.url.len(),
+    };
+    // ` (date, score 0.00, unreachable)`, or for media ` (type, h:mm:ss,
+    // HTTP 200)`.
+    let details =
+        len(&metadata.published_date) + len(&metadata.media_type) + len(&metadata.duration) + 48;
+    let snippet = len(&metadata.snippet) + 4;
+    let thumbnail = len(&metadata.thumbnail) + 16;
+    // At most `max_chars` characters of up to 4 bytes each, and an ellipsis.
+    let page_content = options.page_content_chars.map_or(0, |max_chars| {
+        page_content.len().min(max_chars.saturating_mul(4)) + 8
+    });
+
+    [
+        metadata.title.len(),
+        url,
+        details,
+        snippet,
+        thumbnail,
+        page_content,
+    ]
+    .into_iter()
+    .fold(0, usize::saturating_add)
+}
+
+/// The sources as a JSON array of `{title, url}` objects, in citation order.
+fn sources_json(resp: &PerplexicaSearchResponse) -> String {
+    let sources: Vec<_> = resp
+        .sources
+        .iter()
+        .map(|source| serde_json::json!({ "title": source.metadata.title, "url": source.metadata.url }))
+        .collect();
+    serde_json::to_string_pretty(&sources).unwrap_or_default()
+}
+
+/// Room for [`sources_json`] and its fence: each object's fields at six
+/// bytes per input byte, since a control character escapes to `\u00XX`,
+/// with keys and indentation, plus the fence itself.
+fn sources_json_capacity(resp: &PerplexicaSearchResponse) -> usize {
+    resp.sources
+        .iter()
+        .map(|s| {
+            s.metadata
+                .title
+                .len()
+                .saturating_add(s.metadata.url.len())
+                .saturating_mul(6)
+                + 48
+        })
+        .fold(24, usize::saturating_add)
+}
+
+/// Renders the summary, then a `Sources:` list with one `[n] Title: url`
+/// line per source, keeping the citation numbers the summary uses.
+fn format_text(resp: &PerplexicaSearchResponse, options: &FormatOptions) -> String {
+    let mut text = format!("{}\n\n", resp.message);
+    if let Some(reason) = &resp.interrupted {
+        let _ = write!(text, "{}\n\n", interrupted_note(reason));
+    }
+    if let Some(fallback) = &resp.served_by {
+        let _ = write!(text, "{}\n\n", fallback_note(fallback));
+    }
+
+    let (order, total) = listed_sources(resp, options);
+    if options.omit_sources {
+        text.truncate(text.trim_end().len());
+        text.push('\n');
+    } else if order.is_empty() {
+        text.push_str("Sources: none\n");
+    } else {
+        text.push_str("Sources:\n");
+        for &index in &order {
+            let metadata = &resp.sources[index].metadata;
+            let _ = write!(text, "[{}] {}", index + 1, metadata.title);
+            if let Some(score) = displayed_score(metadata, options) {
+                let _ = write!(text, " ({})", score);
+            }
+            let _ = writeln!(text, ": {}", metadata.url);
+        }
+        if order.len() < total {
+            let _ = writeln!(text, "(showing {} of {} sources)", order.len(), total);
+        }
+    }
+
+    if let Some(elapsed) = options.elapsed {
+        let _ = writeln!(text, "\nSearch completed in {:.1}s", elapsed.as_secs_f64());
+    }
+    if resp.cached {
+        let _ = writeln!(text, "\n{}", cached_note(resp));
+    }
+    if let Some(request_id) = &resp.request_id {
+        let _ = writeln!(text, "\nRequest ID: {}", request_id);
+    }
+
+    text
+}
+
+fn write_sources(
+    markdown: &mut String,
+    resp: &PerplexicaSearchResponse,
+    indices: &[usize],
+    options: &FormatOptions,
+) {
+    // Numbering starts at 1 so list items line up with the inline [n] markers.
+    for &index in indices {
+        let source = &resp.sources[index];
+        let _ = write!(markdown, "{}. {}", index + 1, source.metadata.title);
+        let details: Vec<String> = [
+            non_empty(&source.metadata.published_date).map(str::to_string),
+            displayed_score(&source.metadata, options),
+            displayed_status(index, options),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !details.is_empty() {
+            let _ = write!(markdown, " ({})", details.join(", "));
+        }
+        let _ = writeln!(
+            markdown,
+            " — {}",
+            display_url(&source.metadata.url, options.max_url_chars)
+        );
+
+        if let Some(snippet) = non_empty(&source.metadata.snippet) {
+            let _ = writeln!(markdown, "   {}", collapse_whitespace(snippet));
+        }
+        if let Some(max_chars) = options.page_content_chars {
+            let snippet = trim_snippet(&source.page_content, max_chars);
+            if !snippet.is_empty() {
+                let _ = writeln!(markdown, "   > {}", snippet);
+            }
+        }
+    }
+}
+
+/// The source's score as shown next to it, when scores were requested and
+/// Perplexica sent one.
+fn displayed_score(metadata: &SourceMetadata, options: &FormatOptions) -> Option<String> {
+    let score = metadata.score.filter(|_| options.include_scores)?;
+    Some(format!("score {:.2}", score))
+}
+
+/// What the source's URL answered, when sources were checked.
+fn displayed_status(index: usize, options: &FormatOptions) -> Option<String> {
+    let status = options.source_status.as_ref()?.get(index)?;
+    Some(status.to_string())
+}
+
+/// Lists media results like [`write_sources`], with the media type and
+/// duration after the title and a link to the thumbnail below.
+fn write_media(
+    markdown: &mut String,
+    resp: &PerplexicaSearchResponse,
+    indices: &[usize],
+    options: &FormatOptions,
+) {
+    for &index in indices {
+        let metadata = &resp.sources[index].metadata;
+        let _ = write!(markdown, "{}. {}", index + 1, metadata.title);
+        let details: Vec<String> = [
+            non_empty(&metadata.media_type).map(str::to_string),
+            non_empty(&metadata.duration).map(display_duration),
+            displayed_status(index, options),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !details.is_empty() {
+            let _ = write!(markdown, " ({})", details.join(", "));
+        }
+        let _ = writeln!(
+            markdown,
+            " — {}",
+            display_url(&metadata.url, options.max_url_chars)
+        );
+
+        if let Some(thumbnail) = non_empty(&metadata.thumbnail) {
+            let _ = writeln!(markdown, "   [Thumbnail]({})", thumbnail);
+        }
+    }
+}
+
+/// Shows a duration given in whole seconds as `m:ss` (or `h:mm:ss`); any
+/// other form, such as an already formatted `12:34`, is shown as is.
+fn display_duration(duration: &str) -> String {
+    let Ok(seconds) = duration.parse::<u64>() else {
+        return duration.to_string();
+    };
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Whether each source is cited at least once in the summary.
+fn cited_sources(resp: &PerplexicaSearchResponse) -> Vec<bool> {
+    count_citations(&resp.message, resp.sources.len())
+        .into_iter()
+        .map(|count| count > 0)
+        .collect()
+}
+
+/// Builds a two-turn transcript: the query as the user turn and the summary as
+/// the assistant turn, with the listed sources attached to the assistant turn:
+///
+/// ```json
+/// {
+///   "messages": [
+///     { "role": "user", "content": "<query>" },
+///     {
+///       "role": "assistant",
+///       "content": "<summary>",
+///       "annotations": [
+///         { "type": "source", "number": 1, "title": "...", "url": "...", "snippet": "..." }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// `number` is the citation number used by the `[n]` markers in the summary;
+/// `snippet` is only present when page content was requested, a boolean
+/// `cited` only when cited sources are marked, and `score` only when scores
+/// were requested and Perplexica sent one.
+fn chatml_transcript(
+    query: &str,
+    resp: &PerplexicaSearchResponse,
+    options: &FormatOptions,
+) -> serde_json::Value {
+    let (order, _) = listed_sources(resp, options);
+    let cited = options.mark_cited.then(|| cited_sources(resp));
+    let annotations: Vec<serde_json::Value> = order
+        .iter()
+        .map(|&index| {
+            let source = &resp.sources[index];
+            let mut annotation = serde_json::json!({
+                "type": "source",
+                "number": index + 1,
+                "title": source.metadata.title,
+                "url": source.metadata.url,
+            });
+            if let Some(max_chars) = options.page_content_chars {
+                annotation["snippet"] = trim_snippet(&source.page_content, max_chars).into();
+            }
+            if let Some(cited) = &cited {
+                annotation["cited"] = cited[index].into();
+            }
+            if let Some(score) = source.metadata.score.filter(|_| options.include_scores) {
+                annotation["score"] = score.into();
+            }
+            annotation
+        })
+        .collect();
+
+    serde_json::json!({
+        "messages": [
+            { "role": "user", "content": query },
+            { "role": "assistant", "content": resp.message, "annotations": annotations },
+        ]
+    })
+}
+
+/// Collapses whitespace onto one line and trims to `max_chars` characters,
+/// marking the cut with an ellipsis.
+fn trim_snippet(content: &str, max_chars: usize) -> String {
+    let collapsed = collapse_whitespace(content);
+
+    if collapsed.chars().count() <= max_chars {
+        return collapsed;
+    }
+
+    let end = collapsed
+        .char_indices()
+        .nth(max_chars)
+        .map_or(collapsed.len(), |(end, _)| end);
+    format!("{}…", truncate_on_char_boundary(&collapsed, end).trim_end())
+}
+
+/// Renders `url` as is, or when it is longer than `max_chars` as a markdown
+/// link whose text is the host and path cut down to `max_chars` characters.
+fn display_url(url: &str, max_chars: Option<usize>) -> String {
+    let Some(max_chars) = max_chars.filter(|&max| url.chars().count() > max) else {
+        return url.to_string();
+    };
+
+    let visible = match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.host_str().is_some() => {
+            let start = url.find(parsed.host_str().unwrap_or_default()).unwrap_or(0);
+            url[start..].to_string()
+        }
+        _ => url.to_string(),
+    };
+    let text = match visible.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", truncate_on_char_boundary(&visible, end)),
+        None => visible,
+    };
+
+    let target = url
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29");
+    format!("[{}]({})", text, target)
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn collapse_whitespace(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Extracts the lead of a summary: its first paragraph that isn't a heading,
+/// cut after `max_sentences` sentences.
+///
+/// A sentence ends at `.`, `!` or `?` followed by whitespace and an uppercase
+/// letter, digit or opening quote, or by the end of the paragraph. Decimals and
+/// lowercase continuations such as "e.g. this" therefore don't split one.
+/// Closing quotes and brackets, and `[n]` citations right after the
+/// terminator, stay with their sentence.
+pub fn extract_lead(summary: &str, max_sentences: usize) -> String {
+    let paragraph = summary
+        .split("\n\n")
+        .map(str::trim)
+        .find(|paragraph| !paragraph.is_empty() && !paragraph.starts_with('#'))
+        .map(collapse_whitespace)
+        .unwrap_or_default();
+    let chars: Vec<char> = paragraph.chars().collect();
+
+    let mut sentences = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if !matches!(chars[i], '.' | '!' | '?') {
+            i += 1;
+            continue;
+        }
+
+        let mut end = i + 1;
+        while end < chars.len()
+            && matches!(
+                chars[end],
+                '.' | '!' | '?' | '"' | '\'' | ')' | ']' | '”' | '’'
+            )
+        {
+            end += 1;
+        }
+        let next = loop {
+            let start = end + chars[end..].iter().take_while(|&&c| c == ' ').count();
+            let digits = chars.get(start + 1..).map_or(0, |rest| {
+                rest.iter().take_while(|c| c.is_ascii_digit()).count()
+            });
+            let is_citation = chars.get(start) == Some(&'[')
+                && digits > 0
+                && chars.get(start + 1 + digits) == Some(&']');
+            if !is_citation {
+                break start;
+            }
+            end = start + digits + 2;
+        };
+
+        let is_boundary = next == chars.len()
+            || (next > end
+                && (chars[next].is_uppercase()
+                    || chars[next].is_ascii_digit()
+                    || matches!(chars[next], '"' | '“' | '(' | '*')));
+        if is_boundary {
+            sentences += 1;
+            if sentences == max_sentences {
+                return chars[..end].iter().collect();
+            }
+        }
+        i = end;
+    }
+
+    paragraph
+}
+
+/// Renders the lead of a summary as a preview, pointing at the
+/// `full_summary` structured field when anything was left out.
+pub fn format_lead(summary: &str, max_sentences: usize) -> String {
+    let lead = extract_lead(summary, max_sentences);
+    if lead == collapse_whitespace(summary) {
+        return lead;
+    }
+    format!(
+        "{}\n\n_Preview of the answer. The full answer is in the `full_summary` field of the structured content._",
+        lead
+    )
+}
+
+/// Renders one section per batch query, in order. Failed queries show their
+/// error in place of the result.
+pub fn format_search_batch(
+    results: &[(&str, Result<&PerplexicaSearchResponse, &str>)],
+    options: &FormatOptions,
+) -> String {
+    let mut markdown = String::from("## Batch Search\n\n");
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    let _ = writeln!(
+        markdown,
+        "{} queries, {} succeeded, {} failed.",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+
+    for (number, (query, result)) in results.iter().enumerate() {
+        let _ = write!(markdown, "\n### {}. {}\n\n", number + 1, query);
+        match result {
+            Ok(resp) => markdown.push_str(&format_markdown(resp, options, 4)),
+            Err(error) => {
+                let _ = writeln!(markdown, "**Error:** {}", error);
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Renders the settings a search ran with as a short list, for the top of a
+/// result. The embedding provider is only named when it differs from the chat
+/// model's, and an auto-selected embedding model is marked as such.
+pub fn format_search_metadata(settings: &SearchSettings) -> String {
+    let mut markdown = String::from("**Search metadata**\n\n");
+    let _ = writeln!(markdown, "- Provider: `{}`", settings.provider_id);
+    let _ = writeln!(markdown, "- Chat model: `{}`", settings.chat_model_key);
+    let _ = write!(
+        markdown,
+        "- Embedding model: `{}`",
+        settings.embedding_model_key
+    );
+    let notes: Vec<String> = [
+        (settings.embedding_provider_id != settings.provider_id)
+            .then(|| format!("provider `{}`", settings.embedding_provider_id)),
+        settings
+            .embedding_auto_selected
+            .then(|| "auto-selected".to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !notes.is_empty() {
+        let _ = write!(markdown, " ({})", notes.join(", "));
+    }
+    let _ = writeln!(markdown, "\n- Focus mode: `{}`", settings.focus_mode);
+    let _ = writeln!(
+        markdown,
+        "- Optimization mode: `{}`",
+        settings.optimization_mode
+    );
+    markdown
+}
+
+/// Renders the source the answer cites most often, or explains why there isn't one.
+pub fn format_primary_source(resp: &PerplexicaSearchResponse) -> String {
+    let mut markdown = String::from("## Primary Source\n\n");
+
+    if resp.sources.is_empty() {
+        markdown.push_str("No sources found.\n");
+        return markdown;
+    }
+
+    match most_cited_source(&resp.message, resp.sources.len()) {
+        Some((index, count)) => {
+            let source = &resp.sources[index];
+            let _ = writeln!(
+                markdown,
+                "{}. {} — {}\n\nCited {} {} in the answer.",
+                index + 1,
+                source.metadata.title,
+                source.metadata.url,
+                count,
+                if count == 1 { "time" } else { "times" }
+            );
+        }
+        None => {
+            let _ = writeln!(
+                markdown,
+                "The answer does not cite any of its {} sources.",
+                resp.sources.len()
+            );
+        }
+    }
+
+    markdown
+}
+
+pub fn format_search_diff(query_a: &str, query_b: &str, diff: &SearchDiff) -> String {
+    let mut markdown = String::from("## Search Diff\n\n");
+    let _ = writeln!(markdown, "**A:** {}\n**B:** {}\n", query_a, query_b);
+
+    markdown.push_str("### Summary Changes\n\n");
+    if diff.removed_sentences.is_empty() && diff.added_sentences.is_empty() {
+        markdown.push_str("No sentence-level changes.\n\n");
+    } else {
+        write_list(
+            &mut markdown,
+            "Removed (only in A)",
+            &diff.removed_sentences,
+        );
+        write_list(&mut markdown, "Added (only in B)", &diff.added_sentences);
+    }
+
+    markdown.push_str("### Sources\n\n");
+    write_list(&mut markdown, "Only in A", &diff.sources_only_in_a);
+    write_list(&mut markdown, "Only in B", &diff.sources_only_in_b);
+    write_list(&mut markdown, "Shared", &diff.shared_sources);
+
+    if diff.truncated {
+        let _ = writeln!(
+            markdown,
+            "_Comparison limited to the first {} sentences and sources of each search._",
+            MAX_DIFF_ITEMS
+        );
+    }
+
+    markdown
+}
+
+fn write_list(markdown: &mut String, label: &str, items: &[String]) {
+    let _ = writeln!(markdown, "{}:", label);
+    if items.is_empty() {
+        markdown.push_str("- (none)\n");
+    }
+    for item in items {
+        let _ = writeln!(markdown, "- {}", item);
+    }
+    markdown.push('\n');
+}
+
+/// Renders a cost estimate and the assumptions behind it, or explains that no
+/// pricing table is configured.
+pub fn format_cost_estimate(estimate: Option<&CostEstimate>) -> String {
+    let mut markdown = String::from("## Cost Estimate\n\n");
+    let Some(estimate) = estimate else {
+        markdown.push_str(
+            "No pricing configured. Set PERPLEXICA_PRICING_FILE to a JSON pricing table to estimate search costs.\n",
+        );
+        return markdown;
+    };
+
+    write_model_cost(&mut markdown, "Chat model", &estimate.chat);
+    write_model_cost(&mut markdown, "Embedding model", &estimate.embedding);
+    let _ = writeln!(
+        markdown,
+        "\n**Estimated total: {:.6}**\n\n### Assumptions\n\n\
+         - ~{} prompt tokens from the query, history and system instructions, at about {} characters per token\n\
+         - ~{} tokens of retrieved source content, read by the chat model and embedded along with the query\n\
+         - ~{} tokens of answer\n\
+         - Prices are per 1,000 tokens, from PERPLEXICA_PRICING_FILE; models without a price count as 0",
+        estimate.total(),
+        estimate.prompt_tokens,
+        CHARS_PER_TOKEN,
+        ASSUMED_CONTEXT_TOKENS,
+        ASSUMED_OUTPUT_TOKENS
+    );
+
+    markdown
+}
+
+fn write_model_cost(markdown: &mut String, label: &str, model: &ModelCost) {
+    let _ = write!(
+        markdown,
+        "- {} `{}` (provider `{}`): ~{} input",
+        label, model.key, model.provider_id, model.input_tokens
+    );
+    if model.output_tokens > 0 {
+        let _ = write!(markdown, " + ~{} output", model.output_tokens);
+    }
+    match model.cost {
+        Some(cost) => {
+            let _ = writeln!(markdown, " tokens, {:.6}", cost);
+        }
+        None => markdown.push_str(" tokens, no price configured\n"),
+    }
+}
+
+pub fn format_loadtest_report(report: &LoadtestReport) -> String {
+    let mut markdown = String::from("## Load Test\n\n");
+    let _ = writeln!(
+        markdown,
+        "- Searches: {} at concurrency {}, in {} ms\n- Succeeded: {} ({:.1}%)",
+        report.requests,
+        report.concurrency,
+        report.elapsed.as_millis(),
+        report.succeeded,
+        report.success_rate() * 100.0
+    );
+
+    markdown.push_str("\n### Latency\n\n");
+    for (percentile, latency) in &report.latency_percentiles {
+        let _ = writeln!(markdown, "- p{}: {} ms", percentile, latency.as_millis());
+    }
+
+    // Each kind is a ServiceError kind, as in the error data of a failed search.
+    markdown.push_str("\n### Errors\n\n");
+    if report.errors.is_empty() {
+        markdown.push_str("None\n");
+    }
+    for (kind, count) in &report.errors {
+        let _ = writeln!(markdown, "- {}: {}", kind, count);
+    }
+
+    markdown
+}
+
+/// Renders the counters the server has kept since it started.
+pub fn format_stats(stats: &StatsSnapshot) -> String {
+    let success_rate = match stats.searches {
+        0 => 0.0,
+        searches => stats.succeeded() as f64 / searches as f64 * 100.0,
+    };
+    let mut markdown = String::from("## Server Stats\n\n");
+    let _ = writeln!(
+        markdown,
+        "- Uptime: {}s\n- Searches: {}\n- Succeeded: {} ({:.1}%)\n- Failed: {}\n- Provider listings: {}",
+        stats.uptime.as_secs(),
+        stats.searches,
+        stats.succeeded(),
+        success_rate,
+        stats.failed(),
+        stats.provider_listings
+    );
+
+    markdown.push_str("\n### Failures\n\n");
+    if stats.failures.is_empty() {
+        markdown.push_str("None\n");
+    }
+    for (kind, count) in &stats.failures {
+        let _ = writeln!(markdown, "- {}: {}", kind, count);
+    }
+
+    markdown
+}
+
+/// Summarizes each provider's ID and models as one markdown block.
+pub fn format_providers_summary(providers: &ProvidersResponse) -> String {
+    let mut markdown = format!("Found {} providers available:\n", providers.providers.len());
+    write_providers(&mut markdown, providers, None);
+    markdown
+}
+
+/// Merges the providers of several Perplexica instances into one report,
+/// each labeled with the instance it came from. An instance that failed is
+/// reported in its place, without hiding the others.
+pub fn format_instances_providers(
+    instances: &[(&str, Result<&ProvidersResponse, &str>)],
+) -> String {
+    let answered: Vec<&ProvidersResponse> = instances
+        .iter()
+        .filter_map(|(_, result)| result.ok())
+        .collect();
+    let mut markdown = format!(
+        "Found {} providers across {} of {} instances:\n",
+        answered
+            .iter()
+            .map(|providers| providers.providers.len())
+            .sum::<usize>(),
+        answered.len(),
+        instances.len()
+    );
+    for (label, result) in instances {
+        match result {
+            Ok(providers) => write_providers(&mut markdown, providers, Some(label)),
+            Err(error) => {
+                let _ = writeln!(markdown, "\n## {}\n**Error:** {}", label, error);
+            }
+        }
+    }
+    markdown
+}
+
+/// Writes a section per provider, with `instance` in its heading when set,
+/// then any entries that were skipped.
+fn write_providers(markdown: &mut String, providers: &ProvidersResponse, instance: Option<&str>) {
+    for provider in &providers.providers {
+        let _ = write!(markdown, "\n## {}", provider.name);
+        if let Some(instance) = instance {
+            let _ = write!(markdown, " ({})", instance);
+        }
+        let _ = writeln!(markdown, "\nID: {}", provider.id);
+        write_models(markdown, "Chat Models", &provider.chat_models);
+        write_models(markdown, "Embedding Models", &provider.embedding_models);
+    }
+    if !providers.skipped.is_empty() {
+        let source = instance.unwrap_or("Perplexica");
+        let _ = writeln!(
+            markdown,
+            "\nSkipped {} entries {} sent in an unexpected shape:",
+            providers.skipped.len(),
+            source
+        );
+        for reason in &providers.skipped {
+            let _ = writeln!(markdown, "- {}", reason);
+        }
+    }
+}
+
+/// Writes the model count followed by one indented `- Name (key)` line per model.
+fn write_models(markdown: &mut String, label: &str, models: &[Model]) {
+    let _ = writeln!(markdown, "{}: {}", label, models.len());
+    for model in models {
+        let _ = writeln!(markdown, "  - {} ({})", model.name, model.key);
+    }
+}
+
+/// Reports whether the given provider and model keys exist in the providers
+/// list, naming the available alternatives for anything that doesn't.
+pub fn format_model_validation(
+    providers: &ProvidersResponse,
+    provider_id: &str,
+    chat_model_key: Option<&str>,
+    embedding_model_key: Option<&str>,
+) -> String {
+    let mut report = String::from("## Model Validation\n\n");
+
+    let Some(provider) = providers.providers.iter().find(|p| p.id == provider_id) else {
+        let available = providers
+            .providers
+            .iter()
+            .map(|p| format!("`{}` ({})", p.id, p.name))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            report,
+            "- Provider `{}`: invalid. Available providers: {}",
+            provider_id,
+            join_or_none(&available)
+        );
+        return report;
+    };
+
+    let _ = writeln!(
+        report,
+        "- Provider `{}`: valid ({})",
+        provider.id, provider.name
+    );
+
+    let checks = [
+        (
+            "Chat model",
+            "chat models",
+            chat_model_key,
+            &provider.chat_models,
+        ),
+        (
+            "Embedding model",
+            "embedding models",
+            embedding_model_key,
+            &provider.embedding_models,
+        ),
+    ];
+
+    for (label, plural, key, models) in checks {
+        let Some(key) = key else { continue };
+
+        if models.iter().any(|model| model.key == key) {
+            let _ = writeln!(report, "- {} `{}`: valid", label, key);
+        } else {
+            let _ = writeln!(
+                report,
+                "- {} `{}`: invalid. Available {}: {}",
+                label,
+                key,
+                plural,
+                join_or_none(&model_keys(models))
+            );
+        }
+    }
+
+    report
+}
+
+/// Renders the `perplexica_ping_providers` checklist, ending with whether
+/// every check passed.
+pub fn format_ping_report(checks: &[Check]) -> String {
+    let mut report = String::from("## Provider Ping\n\n");
+    for check in checks {
+        let mark = if check.passed { 'x' } else { ' ' };
+        let _ = writeln!(report, "- [{}] {}: {}", mark, check.label, check.detail);
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if failed == 0 {
+        report.push_str("\n**PASS**: all checks passed\n");
+    } else {
+        let _ = writeln!(
+            report,
+            "\n**FAIL**: {} of {} checks failed",
+            failed,
+            checks.len()
+        );
+    }
+    report
+}
+
+/// Renders the `--check-config` report as plain text for a terminal: a
+/// `PASS` or `FAIL` line per setting, then the verdict.
+pub fn format_config_report(checks: &[Check]) -> String {
+    let mut report = String::new();
+    for check in checks {
+        let mark = if check.passed { "PASS" } else { "FAIL" };
+        let _ = writeln!(report, "{}  {}: {}", mark, check.label, check.detail);
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if failed == 0 {
+        let _ = writeln!(
+            report,
+            "\nConfiguration OK: all {} checks passed",
+            checks.len()
+        );
+    } else {
+        let _ = writeln!(
+            report,
+            "\nConfiguration invalid: {} of {} checks failed",
+            failed,
+            checks.len()
+        );
+    }
+    report
+}
+
+/// Renders `(mode, description)` pairs as a markdown table.
+pub fn format_focus_modes(modes: &[(&str, &str)]) -> String {
+    let mut markdown =
+        String::from("## Focus Modes\n\n| Focus mode | Use it for |\n| --- | --- |\n");
+    for (mode, description) in modes {
+        let _ = writeln!(
+            markdown,
+            "| `{}` | {} |",
+            mode,
+            description.replace('|', "\\|")
+        );
+    }
+    markdown
+}
+
+/// Renders the chat and embedding models [`crate::suggest::suggest_models`]
+/// picked for `task`, or why none could be picked.
+pub fn format_model_suggestion(task: Option<&str>, suggestion: Option<&ModelSuggestion>) -> String {
+    let mut markdown = String::from("## Model Suggestion\n\n");
+
+    let Some(suggestion) = suggestion else {
+        markdown.push_str("No chat models are available on this Perplexica instance.\n");
+        return markdown;
+    };
+
+    if let Some(task) = task.map(str::trim).filter(|task| !task.is_empty()) {
+        let _ = writeln!(markdown, "Task: {}", task);
+    }
+    let _ = writeln!(
+        markdown,
+        "Prioritizing {}.\n",
+        suggestion.priority.description()
+    );
+    let _ = writeln!(
+        markdown,
+        "- `provider_id`: `{}` ({})",
+        suggestion.provider.id, suggestion.provider.name
+    );
+    let _ = writeln!(
+        markdown,
+        "- `chat_model_key`: `{}` ({}, {})",
+        suggestion.chat_model.key,
+        suggestion.chat_model.name,
+        suggestion.tier.description()
+    );
+    match suggestion.embedding_model {
+        Some(model) => {
+            let _ = writeln!(
+                markdown,
+                "- `embedding_model_key`: `{}` ({})",
+                model.key, model.name
+            );
+        }
+        None => markdown.push_str("- `embedding_model_key`: none available on any provider\n"),
+    }
+
+    if !suggestion.alternatives.is_empty() {
+        let alternatives: Vec<String> = suggestion
+            .alternatives
+            .iter()
+            .map(|alternative| format!("`{}`", alternative))
+            .collect();
+        let _ = writeln!(markdown, "\nAlso suitable: {}", alternatives.join(", "));
+    }
+
+    markdown
+}
+
+pub(crate) fn model_keys(models: &[Model]) -> Vec<String> {
+    models
+        .iter()
+        .map(|model| format!("`{}`", model.key))
+        .collect()
+}
+
+pub(crate) fn join_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::Source;
+
+    fn sample_response() -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: "Rust is a systems programming language [1].".to_string(),
+            sources: vec![Source {
+                page_content: "Rust content".to_string(),
+                metadata: SourceMetadata {
+                    title: "Rust".to_string(),
+                    url: "https://www.rust-lang.org".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_search_result_markdown() {
+        let markdown = format_search_result(
+            "",
+            &sample_response(),
+            OutputFormat::Markdown,
+            &FormatOptions::default(),
+        );
+
+        assert_eq!(
+            markdown,
+            "## Summary\n\nRust is a systems programming language [1].\n\n## Sources\n\n1. Rust — https://www.rust-lang.org\n"
+        );
+    }
+
+    #[test]
+    fn test_omit_sources_renders_only_the_summary() {
+        let options = FormatOptions {
+            omit_sources: true,
+            ..Default::default()
+        };
+
+        for resp in [sample_response(), response_with_sources(0)] {
+            let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+            assert_eq!(markdown, format!("## Summary\n\n{}\n", resp.message));
+
+            let text = format_search_result("", &resp, OutputFormat::Text, &options);
+            assert_eq!(text, format!("{}\n", resp.message));
+        }
+
+        let timed = FormatOptions {
+            elapsed: Some(Duration::from_millis(1500)),
+            ..options
+        };
+        let markdown = format_search_result("", &sample_response(), OutputFormat::Markdown, &timed);
+        assert!(
+            markdown.ends_with("[1].\n\n_Search completed in 1.5s_\n"),
+            "{}",
+            markdown
+        );
+
+        // Included by default, including the note when there are none.
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(0),
+            OutputFormat::Markdown,
+            &FormatOptions::default(),
+        );
+        assert!(markdown.ends_with("## Sources\n\nNo sources found.\n"));
+    }
+
+    #[test]
+    fn test_markdown_fits_its_capacity_estimate() {
+        let resp = PerplexicaSearchResponse {
+            message: (1..=50)
+                .map(|n| format!("Claim {} [{}].", n, n))
+                .collect::<Vec<_>>()
+                .join(" "),
+            sources: (1..=50)
+                .map(|n| Source {
+                    page_content: "Plenty of page content, in “quotes” — and then some. ".repeat(20),
+                    metadata: SourceMetadata {
+                        title: format!("A \"quoted\" title for source number {}", n),
+                        url: format!(
+                            "https://www.example.com/articles/{}/a (long) path?utm_source=x&page={}",
+                            n, n
+                        ),
+                        score: Some(0.5),
+                        published_date: Some("2024-05-01T12:00:00Z".to_string()),
+                        snippet: Some("A   short\nsnippet of the page.".to_string()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            interrupted: Some("connection reset".to_string()),
+            served_by: Some("openai/gpt-4o-mini".to_string()),
+            request_id: Some("0123456789abcdef".to_string()),
+            cached: true,
+            ..Default::default()
+        };
+        let full = FormatOptions {
+            page_content_chars: Some(200),
+            include_scores: true,
+            max_url_chars: Some(30),
+            elapsed: Some(Duration::from_millis(1500)),
+            sources_json: true,
+            source_status: Some(vec![Reachability::Unreachable; 50]),
+            ..Default::default()
+        };
+
+        for options in [
+            FormatOptions::default(),
+            FormatOptions {
+                mark_cited: true,
+                max_sources: Some(10),
+                ..full.clone()
+            },
+            full,
+        ] {
+            let estimate = markdown_capacity(&resp, &options, 2);
+            let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+
+            assert!(
+                markdown.len() <= estimate,
+                "{} > {}",
+                markdown.len(),
+                estimate
+            );
+            // Still the buffer reserved up front, so it never reallocated.
+            assert_eq!(markdown.capacity(), estimate);
+        }
+    }
+
+    #[test]
+    fn test_sources_json_capacity_covers_escaped_control_characters() {
+        let resp = PerplexicaSearchResponse {
+            message: "Answer [1].".to_string(),
+            sources: vec![Source {
+                metadata: SourceMetadata {
+                    title: "\u{1}".repeat(200),
+                    url: "https://example.com/\u{1f}".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let options = FormatOptions {
+            sources_json: true,
+            ..Default::default()
+        };
+
+        let estimate = markdown_capacity(&resp, &options, 2);
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+
+        assert!(markdown.contains(&"\\u0001".repeat(200)));
+        assert!(
+            markdown.len() <= estimate,
+            "{} > {}",
+            markdown.len(),
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_sources_json_sidecar_parses_back() {
+        let options = FormatOptions {
+            sources_json: true,
+            max_sources: Some(1),
+            ..Default::default()
+        };
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(2),
+            OutputFormat::Markdown,
+            &options,
+        );
+
+        let (readable, sidecar) = markdown.split_once("\n```json\n").unwrap();
+        assert!(readable.contains("1. Title 1"));
+        let sidecar = sidecar.strip_suffix("\n```\n").unwrap();
+        let sources: Vec<serde_json::Value> = serde_json::from_str(sidecar).unwrap();
+        assert_eq!(
+            sources,
+            [
+                serde_json::json!({ "title": "Title 1", "url": "https://example.com/1" }),
+                serde_json::json!({ "title": "Title 2", "url": "https://example.com/2" }),
+            ]
+        );
+
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(2),
+            OutputFormat::Markdown,
+            &FormatOptions::default(),
+        );
+        assert!(!markdown.contains("```json"));
+    }
+
+    #[test]
+    fn test_markdown_follow_up_questions() {
+        let resp = PerplexicaSearchResponse {
+            follow_ups: Some(vec![
+                "How does Rust manage memory?".to_string(),
+                "  ".to_string(),
+                " Who maintains Rust? ".to_string(),
+            ]),
+            ..sample_response()
+        };
+        let options = FormatOptions {
+            follow_ups: true,
+            ..Default::default()
+        };
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(
+            markdown.ends_with(
+                "1. Rust — https://www.rust-lang.org\n\n## Follow-up Questions\n\n- How does Rust manage memory?\n- Who maintains Rust?\n"
+            ),
+            "{}",
+            markdown
+        );
+
+        // Off unless enabled.
+        let markdown =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+        assert!(!markdown.contains("Follow-up Questions"));
+    }
+
+    #[test]
+    fn test_markdown_omits_absent_follow_up_questions() {
+        let options = FormatOptions {
+            follow_ups: true,
+            ..Default::default()
+        };
+        for follow_ups in [None, Some(vec![]), Some(vec![" ".to_string()])] {
+            let resp = PerplexicaSearchResponse {
+                follow_ups,
+                ..sample_response()
+            };
+            let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+            assert_eq!(
+                markdown,
+                format_search_result(
+                    "",
+                    &sample_response(),
+                    OutputFormat::Markdown,
+                    &FormatOptions::default()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_sources_hint_only_without_sources() {
+        let options = FormatOptions {
+            no_sources_hint: true,
+            ..Default::default()
+        };
+        let hint = format!("\n_{}_\n", NO_SOURCES_HINT);
+
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(0),
+            OutputFormat::Markdown,
+            &options,
+        );
+        assert!(
+            markdown.ends_with(&format!("No sources found.\n{}", hint)),
+            "{}",
+            markdown
+        );
+
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(2),
+            OutputFormat::Markdown,
+            &options,
+        );
+        assert!(!markdown.contains(NO_SOURCES_HINT));
+
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(0),
+            OutputFormat::Markdown,
+            &FormatOptions::default(),
+        );
+        assert!(!markdown.contains(NO_SOURCES_HINT));
+    }
+
+    #[test]
+    fn test_markdown_source_numbers_match_citations() {
+        let resp = PerplexicaSearchResponse {
+            message: "First [1], second [2], third [3].".to_string(),
+            sources: (1..=3)
+                .map(|n| Source {
+                    page_content: String::new(),
+                    metadata: SourceMetadata {
+                        title: format!("Title {}", n),
+                        url: format!("https://example.com/{}", n),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let markdown =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+        let items: Vec<&str> = markdown
+            .lines()
+            .skip_while(|line| *line != "## Sources")
+            .filter(|line| line.chars().next().is_some_and(|c| c.is_ascii_digit()))
+            .collect();
+
+        assert_eq!(items.len(), 3);
+        for (index, item) in items.iter().enumerate() {
+            let n = index + 1;
+            assert_eq!(
+                *item,
+                format!("{}. Title {} — https://example.com/{}", n, n, n)
+            );
+        }
+    }
+
+    #[test]
+    fn test_markdown_source_order_keeps_citation_numbers() {
+        let resp = PerplexicaSearchResponse {
+            message: "A [1] and B [2].".to_string(),
+            sources: ["A", "B"]
+                .iter()
+                .map(|title| Source {
+                    page_content: String::new(),
+                    metadata: SourceMetadata {
+                        title: title.to_string(),
+                        url: format!("https://example.com/{}", title),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let options = FormatOptions {
+            source_order: Some(vec![1, 0]),
+            ..Default::default()
+        };
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+
+        assert!(markdown.ends_with("2. B — https://example.com/B\n1. A — https://example.com/A\n"));
+    }
+
+    fn response_with_sources(count: usize) -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: "Answer.".to_string(),
+            sources: (1..=count)
+                .map(|n| Source {
+                    page_content: format!("Page   content\nfor source {}", n),
+                    metadata: SourceMetadata {
+                        title: format!("Title {}", n),
+                        url: format!("https://example.com/{}", n),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markdown_lists_media_results_separately() {
+        let resp: PerplexicaSearchResponse = serde_json::from_str(
+            r#"{
+                "message": "A talk [1], notes [2] and a longer video [3].",
+                "sources": [
+                    {
+                        "pageContent": "",
+                        "metadata": {
+                            "title": "Rust in 100 Seconds",
+                            "url": "https://www.youtube.com/watch?v=5C_HPTJg5ek",
+                            "img_src": "https://i.ytimg.com/vi/5C_HPTJg5ek/hqdefault.jpg",
+                            "duration": 151,
+                            "mediaType": "video"
+                        }
+                    },
+                    {
+                        "pageContent": "",
+                        "metadata": { "title": "Notes", "url": "https://example.com/notes" }
+                    },
+                    {
+                        "pageContent": "",
+                        "metadata": {
+                            "title": "Conference Keynote",
+                            "url": "https://www.youtube.com/watch?v=abc",
+                            "thumbnail": "https://i.ytimg.com/vi/abc/hqdefault.jpg",
+                            "duration": "1:02:03"
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &Default::default());
+
+        assert_eq!(
+            markdown,
+            "## Summary\n\nA talk [1], notes [2] and a longer video [3].\n\n\
+             ## Sources\n\n\
+             2. Notes — https://example.com/notes\n\
+             \n## Media\n\n\
+             1. Rust in 100 Seconds (video, 2:31) — https://www.youtube.com/watch?v=5C_HPTJg5ek\n   \
+             [Thumbnail](https://i.ytimg.com/vi/5C_HPTJg5ek/hqdefault.jpg)\n\
+             3. Conference Keynote (1:02:03) — https://www.youtube.com/watch?v=abc\n   \
+             [Thumbnail](https://i.ytimg.com/vi/abc/hqdefault.jpg)\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_with_only_media_results() {
+        let mut resp = response_with_sources(1);
+        resp.sources[0].metadata.media_type = Some("image".to_string());
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &Default::default());
+
+        assert!(markdown.contains("## Sources\n\nNo web sources found.\n\n## Media\n\n"));
+        assert!(markdown.contains("1. Title 1 (image) — https://example.com/1\n"));
+    }
+
+    #[test]
+    fn test_markdown_shows_date_and_snippet_when_present() {
+        let mut resp = response_with_sources(2);
+        resp.sources[0].metadata.published_date = Some("2024-05-01".to_string());
+        resp.sources[0].metadata.snippet = Some("A short\nsummary".to_string());
+        resp.sources[1].metadata.published_date = Some(" ".to_string());
+
+        let markdown =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+
+        assert!(markdown.ends_with(
+            "1. Title 1 (2024-05-01) — https://example.com/1\n   A short summary\n2. Title 2 — https://example.com/2\n"
+        ));
+    }
+
+    #[test]
+    fn test_include_scores_sorts_and_shows_scores() {
+        let mut resp = response_with_sources(3);
+        resp.sources[0].metadata.score = Some(0.42);
+        resp.sources[1].metadata.published_date = Some("2024-05-01".to_string());
+        resp.sources[2].metadata.score = Some(0.875);
+        resp.sources[2].metadata.published_date = Some("2023-01-01".to_string());
+        let options = FormatOptions {
+            include_scores: true,
+            ..Default::default()
+        };
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(
+            markdown.ends_with(
+                "3. Title 3 (2023-01-01, score 0.88) — https://example.com/3
+\
+                 1. Title 1 (score 0.42) — https://example.com/1
+\
+                 2. Title 2 (2024-05-01) — https://example.com/2
+"
+            ),
+            "{}",
+            markdown
+        );
+
+        let text = format_search_result("", &resp, OutputFormat::Text, &options);
+        assert!(
+            text.contains(
+                "[3] Title 3 (score 0.88): https://example.com/3\n[1] Title 1 (score 0.42)"
+            ),
+            "{}",
+            text
+        );
+
+        let chatml: serde_json::Value = serde_json::from_str(&format_search_result(
+            "",
+            &resp,
+            OutputFormat::ChatMl,
+            &options,
+        ))
+        .unwrap();
+        let annotations = &chatml["messages"][1]["annotations"];
+        assert_eq!(annotations[0]["score"], 0.875);
+        assert!(annotations[2].get("score").is_none());
+
+        // Scores stay hidden, and the order unchanged, unless requested.
+        let plain =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+        assert!(!plain.contains("score"));
+        assert!(plain.contains("1. Title 1 — https://example.com/1\n2. Title 2"));
+    }
+
+    #[test]
+    fn test_markdown_shortens_long_urls() {
+        let long_url = format!(
+            "https://docs.example.com/guides/{}/index.html?ref=search",
+            "a".repeat(60)
+        );
+        let mut resp = response_with_sources(2);
+        resp.sources[0].metadata.url = long_url.clone();
+        let options = FormatOptions {
+            max_url_chars: Some(30),
+            ..Default::default()
+        };
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+
+        assert!(markdown.contains(&format!(
+            "1. Title 1 — [docs.example.com/guides/aaaaaa…]({})\n",
+            long_url
+        )));
+        assert!(markdown.contains("2. Title 2 — https://example.com/2\n"));
+
+        let json = format_search_result("", &resp, OutputFormat::Json, &options);
+        assert!(json.contains(&format!("\"url\": \"{}\"", long_url)));
+    }
+
+    #[test]
+    fn test_display_url_escapes_link_target() {
+        assert_eq!(
+            display_url("https://en.wikipedia.org/wiki/Rust_(language)", Some(20)),
+            "[en.wikipedia.org/wik…](https://en.wikipedia.org/wiki/Rust_%28language%29)"
+        );
+        assert_eq!(
+            display_url("not a url at all", Some(5)),
+            "[not a…](not%20a%20url%20at%20all)"
+        );
+        assert_eq!(display_url("https://a.io", None), "https://a.io");
+    }
+
+    #[test]
+    fn test_truncation_keeps_multibyte_characters_whole() {
+        assert_eq!(trim_snippet("Crème brûlée 🍮 recipe", 12), "Crème brûlée…");
+        assert_eq!(
+            trim_snippet("Crème brûlée 🍮 recipe", 14),
+            "Crème brûlée 🍮…"
+        );
+        assert_eq!(
+            display_url("https://example.com/café/👍", Some(17)),
+            "[example.com/café/…](https://example.com/café/👍)"
+        );
+
+        let mut resp = response_with_sources(1);
+        resp.message = "Ünïcödé 👩‍👩‍👧 ".repeat(200);
+        for max_chars in [150, 151, 152, 153] {
+            let (output, truncated) = format_search_result_capped(
+                "",
+                &resp,
+                OutputFormat::Markdown,
+                &FormatOptions::default(),
+                Some(max_chars),
+            );
+            assert!(truncated);
+            assert!(output.chars().count() <= max_chars);
+            assert!(output.contains(TRUNCATION_MARKER));
+        }
+    }
+
+    #[test]
+    fn test_markdown_max_sources_truncation_note() {
+        let options = FormatOptions {
+            max_sources: Some(2),
+            ..Default::default()
+        };
+
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(5),
+            OutputFormat::Markdown,
+            &options,
+        );
+
+        assert!(markdown.contains("2. Title 2 — https://example.com/2\n"));
+        assert!(!markdown.contains("Title 3"));
+        assert!(markdown.ends_with("\n(showing 2 of 5 sources)\n"));
+
+        let options = FormatOptions {
+            max_sources: Some(10),
+            ..Default::default()
+        };
+        let markdown = format_search_result(
+            "",
+            &response_with_sources(5),
+            OutputFormat::Markdown,
+            &options,
+        );
+        assert!(!markdown.contains("showing"));
+    }
+
+    #[test]
+    fn test_markdown_page_content_toggle() {
+        let resp = response_with_sources(1);
+
+        let without =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+        assert!(!without.contains("Page content"));
+
+        let options = FormatOptions {
+            page_content_chars: Some(12),
+            ..Default::default()
+        };
+        let with = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(with.ends_with("1. Title 1 — https://example.com/1\n   > Page content…\n"));
+
+        let options = FormatOptions {
+            page_content_chars: Some(500),
+            ..Default::default()
+        };
+        let with = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(with.ends_with("   > Page content for source 1\n"));
+    }
+
+    #[test]
+    fn test_capped_output_cuts_summary_and_keeps_sources() {
+        let mut resp = response_with_sources(3);
+        resp.message = format!("Cited [1]. {}", "word ".repeat(2_000));
+        let options = FormatOptions::default();
+
+        let (full, truncated) =
+            format_search_result_capped("", &resp, OutputFormat::Markdown, &options, None);
+        assert!(!truncated);
+        assert!(full.chars().count() > 10_000);
+
+        for format in [
+            OutputFormat::Markdown,
+            OutputFormat::Json,
+            OutputFormat::ChatMl,
+        ] {
+            let (output, truncated) =
+                format_search_result_capped("", &resp, format, &options, Some(1_000));
+            assert!(truncated, "{:?}", format);
+            assert!(output.chars().count() <= 1_000, "{:?}", format);
+            assert!(output.contains("Cited [1]."), "{:?}", format);
+            assert!(output.contains(TRUNCATION_MARKER), "{:?}", format);
+            assert!(output.contains("https://example.com/3"), "{:?}", format);
+        }
+
+        // Output that already fits is left alone.
+        let (output, truncated) =
+            format_search_result_capped("", &resp, OutputFormat::Markdown, &options, Some(20_000));
+        assert!(!truncated);
+        assert_eq!(output, full);
+    }
+
+    #[test]
+    fn test_markdown_timing_footer() {
+        let resp = response_with_sources(1);
+
+        let without =
+            format_search_result("", &resp, OutputFormat::Markdown, &FormatOptions::default());
+        assert!(!without.contains("Search completed"));
+
+        let options = FormatOptions {
+            elapsed: Some(Duration::from_millis(3_240)),
+            ..Default::default()
+        };
+        let with = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(with.ends_with("https://example.com/1\n\n_Search completed in 3.2s_\n"));
+    }
+
+    #[test]
+    fn test_markdown_mark_cited_splits_sources() {
+        let mut resp = response_with_sources(4);
+        resp.message = "One [1]. Three [3][1].".to_string();
+        let options = FormatOptions {
+            source_order: Some(vec![3, 2, 1, 0]),
+            mark_cited: true,
+            ..Default::default()
+        };
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+
+        assert!(markdown.ends_with(
+            "## Sources\n\n3. Title 3 — https://example.com/3\n1. Title 1 — https://example.com/1\n\n### Additional sources\n\n4. Title 4 — https://example.com/4\n2. Title 2 — https://example.com/2\n"
+        ));
+
+        resp.message = "Nothing cited.".to_string();
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &options);
+        assert!(markdown.contains(
+            "## Sources\n\nThe summary does not cite any of these sources.\n\n### Additional sources\n\n4. Title 4"
+        ));
+    }
+
+    #[test]
+    fn test_chatml_mark_cited() {
+        let mut resp = response_with_sources(2);
+        resp.message = "Second [2].".to_string();
+        let options = FormatOptions {
+            mark_cited: true,
+            ..Default::default()
+        };
+
+        let chatml = format_search_result("q", &resp, OutputFormat::ChatMl, &options);
+        let parsed: serde_json::Value = serde_json::from_str(&chatml).unwrap();
+        let annotations = &parsed["messages"][1]["annotations"];
+
+        assert_eq!(annotations[0]["cited"], false);
+        assert_eq!(annotations[1]["cited"], true);
+    }
+
+    #[test]
+    fn test_search_warnings_for_omitted_and_trimmed_sources() {
+        let options = FormatOptions {
+            max_sources: Some(2),
+            page_content_chars: Some(12),
+            ..Default::default()
+        };
+
+        let warnings = search_warnings(&response_with_sources(3), OutputFormat::Markdown, &options);
+
+        assert_eq!(
+            warnings,
+            vec![
+                "1 of 3 sources omitted by max_sources",
+                "Page content trimmed to 12 characters for 2 sources",
+            ]
+        );
+        assert!(
+            search_warnings(
+                &response_with_sources(3),
+                OutputFormat::Markdown,
+                &FormatOptions::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_search_warnings_for_json_ignored_options() {
+        let options = FormatOptions {
+            max_sources: Some(2),
+            mark_cited: true,
+            ..Default::default()
+        };
+
+        let warnings = search_warnings(&response_with_sources(3), OutputFormat::Json, &options);
+
+        assert_eq!(
+            warnings,
+            vec!["max_sources, mark_cited ignored by the json output format"]
+        );
+    }
+
+    #[test]
+    fn test_format_search_batch() {
+        let resp = sample_response();
+        let results = [
+            ("What is Rust?", Ok(&resp)),
+            ("What is Go?", Err("Go request failed: timed out")),
+        ];
+
+        let markdown = format_search_batch(&results, &FormatOptions::default());
+
+        assert_eq!(
+            markdown,
+            "## Batch Search\n\n2 queries, 1 succeeded, 1 failed.\n\n\
+             ### 1. What is Rust?\n\n\
+             #### Summary\n\nRust is a systems programming language [1].\n\n\
+             #### Sources\n\n1. Rust — https://www.rust-lang.org\n\n\
+             ### 2. What is Go?\n\n**Error:** Go request failed: timed out\n"
+        );
+    }
+
+    #[test]
+    fn test_format_search_result_json() {
+        let json = format_search_result(
+            "",
+            &sample_response(),
+            OutputFormat::Json,
+            &FormatOptions::default(),
+        );
+
+        let parsed: PerplexicaSearchResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed.message,
+            "Rust is a systems programming language [1]."
+        );
+        assert_eq!(parsed.sources.len(), 1);
+        assert_eq!(parsed.sources[0].metadata.url, "https://www.rust-lang.org");
+    }
+
+    #[test]
+    fn test_format_search_result_text() {
+        let options = FormatOptions {
+            source_order: Some(vec![1, 0, 2]),
+            max_sources: Some(2),
+            mark_cited: true,
+            ..Default::default()
+        };
+        let resp = PerplexicaSearchResponse {
+            served_by: Some("ollama".to_string()),
+            ..response_with_sources(3)
+        };
+
+        let text = format_search_result("", &resp, OutputFormat::Text, &options);
+
+        assert_eq!(
+            text,
+            "Answer.\n\n\
+             Served by fallback provider ollama after the requested provider failed\n\n\
+             Sources:\n\
+             [2] Title 2: https://example.com/2\n\
+             [1] Title 1: https://example.com/1\n\
+             (showing 2 of 3 sources)\n"
+        );
+        for line in text.lines() {
+            assert!(
+                !line.starts_with(['#', '-', '*', '_', '>']),
+                "markdown marker in {:?}",
+                line
+            );
+        }
+        assert_eq!(
+            search_warnings(&resp, OutputFormat::Text, &options),
+            [
+                "Served by fallback provider ollama after the requested provider failed",
+                "mark_cited ignored by the text output format",
+                "1 of 3 sources omitted by max_sources",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_search_result_chatml() {
+        let options = FormatOptions {
+            source_order: Some(vec![1, 0]),
+            max_sources: Some(1),
+            ..Default::default()
+        };
+
+        let chatml = format_search_result(
+            "What is Rust?",
+            &response_with_sources(2),
+            OutputFormat::ChatMl,
+            &options,
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&chatml).unwrap();
+
+        assert_eq!(
+            parsed,
+            serde_json::json!({
+                "messages": [
+                    { "role": "user", "content": "What is Rust?" },
+                    {
+                        "role": "assistant",
+                        "content": "Answer.",
+                        "annotations": [
+                            {
+                                "type": "source",
+                                "number": 2,
+                                "title": "Title 2",
+                                "url": "https://example.com/2"
+                            }
+                        ]
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_cost_estimate() {
+        let model = |key: &str, output_tokens, cost| ModelCost {
+            provider_id: "openai".to_string(),
+            key: key.to_string(),
+            input_tokens: 4_010,
+            output_tokens,
+            cost,
+        };
+        let estimate = CostEstimate {
+            prompt_tokens: 10,
+            chat: model("gpt-4o-mini", 500, Some(0.0125)),
+            embedding: model("nomic-embed", 0, None),
+        };
+
+        let markdown = format_cost_estimate(Some(&estimate));
+
+        assert!(markdown.starts_with(
+            "## Cost Estimate\n\n\
+             - Chat model `gpt-4o-mini` (provider `openai`): ~4010 input + ~500 output tokens, 0.012500\n\
+             - Embedding model `nomic-embed` (provider `openai`): ~4010 input tokens, no price configured\n\
+             \n**Estimated total: 0.012500**\n"
+        ));
+        assert!(markdown.contains("- ~10 prompt tokens"));
+        assert!(format_cost_estimate(None).contains("No pricing configured"));
+    }
+
+    #[test]
+    fn test_extract_lead_from_multi_paragraph_summary() {
+        let summary = "## Overview\n\nRust is a systems language [1]. It was first released in \
+                       2015, e.g. for Firefox.\n  It has version 1.2 too [2][3]. \"Safety\" matters!\n\n\
+                       Second paragraph. Never shown.";
+
+        assert_eq!(extract_lead(summary, 1), "Rust is a systems language [1].");
+        assert_eq!(
+            extract_lead(summary, 2),
+            "Rust is a systems language [1]. It was first released in 2015, e.g. for Firefox."
+        );
+        assert_eq!(
+            extract_lead(summary, 3),
+            "Rust is a systems language [1]. It was first released in 2015, e.g. for Firefox. \
+             It has version 1.2 too [2][3]."
+        );
+        assert_eq!(
+            extract_lead(summary, 10),
+            "Rust is a systems language [1]. It was first released in 2015, e.g. for Firefox. \
+             It has version 1.2 too [2][3]. \"Safety\" matters!"
+        );
+    }
+
+    #[test]
+    fn test_extract_lead_keeps_trailing_citations_with_sentence() {
+        assert_eq!(
+            extract_lead("First claim. [1] [2] Second claim.", 1),
+            "First claim. [1] [2]"
+        );
+        assert_eq!(extract_lead("No terminator here", 1), "No terminator here");
+        assert_eq!(extract_lead("", 2), "");
+    }
+
+    #[test]
+    fn test_format_lead_points_to_full_summary_only_when_shortened() {
+        assert!(format_lead("One. Two.", 1).starts_with("One.\n\n_Preview"));
+        assert!(format_lead("One. Two.", 1).contains("`full_summary`"));
+        assert_eq!(format_lead("One. Two.", 2), "One. Two.");
+    }
+
+    #[test]
+    fn test_format_loadtest_report() {
+        let report = LoadtestReport {
+            requests: 4,
+            concurrency: 2,
+            succeeded: 3,
+            elapsed: std::time::Duration::from_millis(120),
+            latency_percentiles: vec![(50, std::time::Duration::from_millis(25))],
+            errors: [("upstream_status".to_string(), 1)].into(),
+        };
+
+        assert_eq!(
+            format_loadtest_report(&report),
+            "## Load Test\n\n\
+             - Searches: 4 at concurrency 2, in 120 ms\n\
+             - Succeeded: 3 (75.0%)\n\
+             \n### Latency\n\n- p50: 25 ms\n\
+             \n### Errors\n\n- upstream_status: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_format_stats() {
+        let mut stats = StatsSnapshot {
+            uptime: std::time::Duration::from_secs(90),
+            searches: 0,
+            failures: Default::default(),
+            provider_listings: 0,
+            latency_buckets: Vec::new(),
+            latency_sum: std::time::Duration::ZERO,
+        };
+        assert!(
+            format_stats(&stats).contains("- Succeeded: 0 (0.0%)\n"),
+            "{}",
+            format_stats(&stats)
+        );
+        assert!(format_stats(&stats).ends_with("### Failures\n\nNone\n"));
+
+        stats.searches = 4;
+        stats.failures = [("upstream_status".to_string(), 1)].into();
+        stats.provider_listings = 2;
+        assert_eq!(
+            format_stats(&stats),
+            "## Server Stats\n\n\
+             - Uptime: 90s\n\
+             - Searches: 4\n\
+             - Succeeded: 3 (75.0%)\n\
+             - Failed: 1\n\
+             - Provider listings: 2\n\
+             \n### Failures\n\n- upstream_status: 1\n"
+        );
+    }
+
+    const RAW_RESPONSE: &str = r#"{
+        "message": "Answer [1].",
+        "sources": [
+            {
+                "pageContent": "Full page text\nwith   spacing",
+                "metadata": {
+                    "title": "Title",
+                    "url": "https://example.com",
+                    "score": 0.5,
+                    "publishedDate": "2024-05-01",
+                    "author": "Someone",
+                    "tags": ["a", "b"]
+                },
+                "id": 7
+            }
+        ],
+        "usage": { "tokens": 1234 }
+    }"#;
+
+    #[test]
+    fn test_full_json_round_trips_everything() {
+        let resp: PerplexicaSearchResponse = serde_json::from_str(RAW_RESPONSE).unwrap();
+
+        let full = format_search_result("", &resp, OutputFormat::FullJson, &Default::default());
+
+        let original: serde_json::Value = serde_json::from_str(RAW_RESPONSE).unwrap();
+        let round_tripped: serde_json::Value = serde_json::from_str(&full).unwrap();
+        assert_eq!(round_tripped, original);
+    }
+
+    #[test]
+    fn test_json_leaves_out_unrecognized_fields() {
+        let resp: PerplexicaSearchResponse = serde_json::from_str(RAW_RESPONSE).unwrap();
+
+        let json = format_search_result("", &resp, OutputFormat::Json, &Default::default());
+
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "message": "Answer [1].",
+                "sources": [{
+                    "pageContent": "Full page text\nwith   spacing",
+                    "metadata": {
+                        "title": "Title",
+                        "url": "https://example.com",
+                        "score": 0.5,
+                        "publishedDate": "2024-05-01"
+                    }
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_markdown_handles_large_payloads() {
+        let mut resp = response_with_sources(1);
+        resp.message = "x".repeat(3 << 20);
+        resp.sources[0].metadata.title = "t".repeat(1 << 20);
+
+        let markdown = format_search_result("", &resp, OutputFormat::Markdown, &Default::default());
+
+        assert!(markdown.len() > 4 << 20);
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!("markdown".parse(), Ok(OutputFormat::Markdown));
+        assert_eq!("json".parse(), Ok(OutputFormat::Json));
+        assert_eq!("full_json".parse(), Ok(OutputFormat::FullJson));
+        assert_eq!("chatml".parse(), Ok(OutputFormat::ChatMl));
+        assert_eq!("text".parse(), Ok(OutputFormat::Text));
+        assert!(
+            "yaml"
+                .parse::<OutputFormat>()
+                .unwrap_err()
+                .contains("markdown, json")
+        );
+    }
+
+    #[test]
+    fn test_search_metadata_names_a_separate_embedding_provider() {
+        let settings = SearchSettings {
+            provider_id: "ollama".to_string(),
+            chat_model_key: "llama3:8b".to_string(),
+            embedding_provider_id: "openai".to_string(),
+            embedding_model_key: "text-embedding-3-small".to_string(),
+            focus_mode: "webSearch".to_string(),
+            optimization_mode: "speed".to_string(),
+            embedding_auto_selected: true,
+        };
+
+        assert_eq!(
+            format_search_metadata(&settings),
+            "**Search metadata**\n\n\
+             - Provider: `ollama`\n\
+             - Chat model: `llama3:8b`\n\
+             - Embedding model: `text-embedding-3-small` (provider `openai`, auto-selected)\n\
+             - Focus mode: `webSearch`\n\
+             - Optimization mode: `speed`\n"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Who said a chat history turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Human,
+    Assistant,
+}
+
+impl Role {
+    pub const VALID_VALUES: &'static [&'static str] = &["human", "assistant"];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Assistant => "assistant",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "human" => Ok(Self::Human),
+            "assistant" => Ok(Self::Assistant),
+            _ => Err(format!("unknown role '{}'", value)),
+        }
+    }
+}
+
+/// One chat history turn. On the wire it is Perplexica's `[role, message]`
+/// pair, the same flat form `perplexica_search` takes, so the typed and flat
+/// forms convert both ways.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct HistoryTurn {
+    pub role: Role,
+    pub message: String,
+}
+
+impl TryFrom<&[String]> for HistoryTurn {
+    type Error = String;
+
+    /// Reads a `[role, message]` pair; the error says what is wrong with it.
+    fn try_from(entry: &[String]) -> Result<Self, Self::Error> {
+        let [role, message] = entry else {
+            return Err(format!("expected 2 elements, got {}", entry.len()));
+        };
+        Ok(Self {
+            role: role.parse()?,
+            message: message.clone(),
+        })
+    }
+}
+
+impl TryFrom<Vec<String>> for HistoryTurn {
+    type Error = String;
+
+    fn try_from(entry: Vec<String>) -> Result<Self, Self::Error> {
+        Self::try_from(entry.as_slice())
+    }
+}
+
+impl From<HistoryTurn> for Vec<String> {
+    fn from(turn: HistoryTurn) -> Self {
+        vec![turn.role.to_string(), turn.message]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn turns() -> Vec<HistoryTurn> {
+        vec![
+            HistoryTurn {
+                role: Role::Human,
+                message: "hi".to_string(),
+            },
+            HistoryTurn {
+                role: Role::Assistant,
+                message: "Hello! How can I help?".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_history_turns_serialize_as_pairs() {
+        let json = serde_json::to_value(turns()).unwrap();
+        assert_eq!(
+            json,
+            json!([["human", "hi"], ["assistant", "Hello! How can I help?"]])
+        );
+
+        let parsed: Vec<HistoryTurn> = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, turns());
+    }
+
+    #[test]
+    fn test_history_turns_convert_from_the_flat_form() {
+        let flat: Vec<Vec<String>> = turns().into_iter().map(Vec::from).collect();
+        assert_eq!(
+            flat,
+            [["human", "hi"], ["assistant", "Hello! How can I help?"]]
+        );
+        let typed: Vec<HistoryTurn> = flat
+            .into_iter()
+            .map(HistoryTurn::try_from)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(typed, turns());
+
+        let wrong_role = ["system".to_string(), "hi".to_string()];
+        assert_eq!(
+            HistoryTurn::try_from(&wrong_role[..]).unwrap_err(),
+            "unknown role 'system'"
+        );
+        assert_eq!(
+            HistoryTurn::try_from(vec!["human".to_string()]).unwrap_err(),
+            "expected 2 elements, got 1"
+        );
+        assert!(serde_json::from_value::<HistoryTurn>(json!(["Human", "hi"])).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::borrow::Cow;
+
+/// Converts the inline HTML some Perplexica setups leave in summaries to
+/// markdown: `<b>`/`<strong>` become `**`, `<i>`/`<em>` `*`, `<code>` a
+/// backtick, `<a href>` a markdown link, `<br>` a line break and `<p>` a
+/// paragraph break. Other tags are dropped, keeping their text, and common
+/// entities are decoded.
+///
+/// Only known HTML elements count as tags, and only when their attributes
+/// are `name=value` pairs, so `Vec<String>` and `a<b and c>d` are kept.
+/// Backtick code spans and fences are copied untouched. Text without tags or
+/// entities is returned as is.
+pub fn html_to_markdown(text: &str) -> Cow<'_, str> {
+    if !text.contains(['<', '&']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut markdown = String::with_capacity(text.len());
+    // The href of each open `<a>`, or None for an anchor without one.
+    let mut links: Vec<Option<String>> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['<', '&', '`']) {
+        markdown.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with('`') {
+            let len = code_len(rest);
+            markdown.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        if rest.starts_with('&') {
+            match decode_entity(rest) {
+                Some((decoded, len)) => {
+                    markdown.push(decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    markdown.push('&');
+                    rest = &rest[1..];
+                }
+            }
+            continue;
+        }
+
+        let Some((tag, len)) = Tag::parse(rest) else {
+            markdown.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[len..];
+        match (tag.name.as_str(), tag.closing) {
+            ("b" | "strong", _) => markdown.push_str("**"),
+            ("i" | "em", _) => markdown.push('*'),
+            ("code", _) => markdown.push('`'),
+            ("br", _) => markdown.push('\n'),
+            ("p", false) if !markdown.is_empty() && !markdown.ends_with("\n\n") => {
+                markdown.push_str(if markdown.ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                });
+            }
+            ("a", false) => {
+                let href = tag.attribute("href").map(decode_entities);
+                if href.is_some() {
+                    markdown.push('[');
+                }
+                links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = links.pop() {
+                    markdown.push_str("](");
+                    markdown.push_str(&href);
+                    markdown.push(')');
+                }
+            }
+            _ => {}
+        }
+    }
+    markdown.push_str(rest);
+
+    Cow::Owned(markdown)
+}
+
+/// The length of the code span or fence `text` starts with: up to and
+/// including the next run of as many backticks as it opens with. A run that
+/// is never closed is just backticks.
+fn code_len(text: &str) -> usize {
+    let ticks = text.len() - text.trim_start_matches('`').len();
+    let mut offset = ticks;
+    while let Some(start) = text[offset..].find('`') {
+        let start = offset + start;
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == ticks {
+            return start + run;
+        }
+        offset = start + run;
+    }
+    ticks
+}
+
+/// The elements `html_to_markdown` treats as tags. Any other `<name>`, such
+/// as a generic type parameter, is kept as text.
+const ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "font",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "tt",
+    "u",
+    "ul",
+];
+
+/// A start or end tag, e.g. `<a href="...">` or `</b>`.
+struct Tag<'a> {
+    /// Lowercased.
+    name: String,
+    closing: bool,
+    attributes: &'a str,
+}
+
+impl<'a> Tag<'a> {
+    /// Parses the tag `text` starts with, returning it and its length. Text
+    /// where `<` isn't followed by a known element name, `name=value`
+    /// attributes and a closing `>` isn't a tag.
+    fn parse(text: &'a str) -> Option<(Self, usize)> {
+        let end = text.find('>')?;
+        let inner = &text[1..end];
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+        let name_len = inner
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(inner.len());
+        if !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let attributes = &inner[name_len..];
+        if !attributes.is_empty() && !attributes.starts_with([' ', '\t', '\n', '/']) {
+            return None;
+        }
+
+        let name = inner[..name_len].to_ascii_lowercase();
+        if !ELEMENTS.contains(&name.as_str()) {
+            return None;
+        }
+
+        let tag = Tag {
+            name,
+            closing,
+            attributes: attributes.trim_end_matches('/'),
+        };
+        if (tag.closing && !tag.attributes.trim().is_empty())
+            || !tag.attribute_pairs().all(|(key, value)| {
+                value.is_some()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+            })
+        {
+            return None;
+        }
+        Some((tag, end + 1))
+    }
+
+    /// The value of attribute `name`, quoted or not.
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attribute_pairs()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.unwrap_or_default())
+    }
+
+    /// Each attribute's name and value, or None for one without `=`.
+    fn attribute_pairs(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> {
+        let mut rest = self.attributes.trim_start();
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let key_len = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len())
+                .max(1);
+            let key = &rest[..key_len];
+            rest = rest[key_len..].trim_start();
+
+            let mut value = None;
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                let (parsed, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let after = &after[1..];
+                        let close = after.find(quote).unwrap_or(after.len());
+                        (&after[..close], after.get(close + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let close = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..close], &after[close..])
+                    }
+                };
+                value = Some(parsed);
+                rest = remaining.trim_start();
+            }
+            Some((key, value))
+        })
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    html_to_markdown(text).into_owned()
+}
+
+/// Decodes the entity `text` starts with, returning the character and the
+/// entity's length.
+fn decode_entity(text: &str) -> Option<(char, usize)> {
+    let end = text.bytes().take(12).position(|byte| byte == b';')?;
+    let name = &text[1..end];
+    let decoded = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((decoded, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converts_bold_and_italics() {
+        assert_eq!(
+            html_to_markdown("Rust is <b>fast</b> and <strong>safe</strong>, <em>really</em>."),
+            "Rust is **fast** and **safe**, *really*."
+        );
+    }
+
+    #[test]
+    fn test_converts_links() {
+        assert_eq!(
+            html_to_markdown(
+                r#"See <a href="https://www.rust-lang.org/?a=1&amp;b=2" target="_blank">the site</a> [1]."#
+            ),
+            "See [the site](https://www.rust-lang.org/?a=1&b=2) [1]."
+        );
+        assert_eq!(
+            html_to_markdown("<A HREF='https://a.com'>A</A> and <a name=x>B</a>"),
+            "[A](https://a.com) and B"
+        );
+    }
+
+    #[test]
+    fn test_converts_line_and_paragraph_breaks() {
+        assert_eq!(
+            html_to_markdown("One<br>Two<br/>Three<br />Four"),
+            "One\nTwo\nThree\nFour"
+        );
+        assert_eq!(
+            html_to_markdown("<p>First.</p><p>Second.</p>"),
+            "First.\n\nSecond."
+        );
+    }
+
+    #[test]
+    fn test_strips_other_tags_and_decodes_entities() {
+        assert_eq!(
+            html_to_markdown("<span class=\"x\">Tom &amp; Jerry&#39;s &lt;show&gt;</span>"),
+            "Tom & Jerry's <show>"
+        );
+        assert_eq!(
+            html_to_markdown("1 < 2 && 3 > 2, <3 and AT&T"),
+            "1 < 2 && 3 > 2, <3 and AT&T"
+        );
+    }
+
+    #[test]
+    fn test_keeps_generics_and_comparisons() {
+        assert_eq!(
+            html_to_markdown("Use Vec<String> or HashMap<K, V> &amp; friends."),
+            "Use Vec<String> or HashMap<K, V> & friends."
+        );
+        assert_eq!(
+            html_to_markdown("If a<b and c>d, then <b>stop</b>."),
+            "If a<b and c>d, then **stop**."
+        );
+        assert_eq!(html_to_markdown("x<p>y</p q>"), "x\n\ny</p q>");
+    }
+
+    #[test]
+    fn test_leaves_code_untouched() {
+        assert_eq!(
+            html_to_markdown("Call `parse::<b>()` or ``a `<i>` b`` &amp; <i>go</i>"),
+            "Call `parse::<b>()` or ``a `<i>` b`` & *go*"
+        );
+        assert_eq!(
+            html_to_markdown("<b>Example</b>:\n```html\n<b>bold</b> &amp;\n```\nDone<br>"),
+            "**Example**:\n```html\n<b>bold</b> &amp;\n```\nDone\n"
+        );
+        // An unclosed backtick is just a backtick.
+        assert_eq!(html_to_markdown("a ` <b>b</b>"), "a ` **b**");
+    }
+
+    #[test]
+    fn test_plain_text_is_unchanged() {
+        let text = "Rust is a systems language [1].\n\nIt is **fast**.";
+        assert!(matches!(html_to_markdown(text), Cow::Borrowed(unchanged) if unchanged == text));
+    }
+}
@@ -0,0 +1,99 @@
+/// Checks that `tag` looks like a BCP 47 language tag and returns it in its
+/// conventional case, e.g. `pt_br` becomes `pt-BR` and `zh-hant` `zh-Hant`.
+///
+/// The check is loose: a 2–3 letter language, then any number of 1–8
+/// character alphanumeric subtags. Whether the language exists is left to
+/// the model.
+pub fn normalize_language_tag(tag: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "'{}' is not a language tag; expected a BCP 47 tag such as 'en', 'de-DE' or 'zh-Hant'",
+            tag
+        )
+    };
+    let mut subtags = tag.trim().split(['-', '_']);
+    let language = subtags.next().unwrap_or_default();
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid());
+    }
+
+    let mut normalized = language.to_ascii_lowercase();
+    for subtag in subtags {
+        if !(1..=8).contains(&subtag.len()) || !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+        normalized.push('-');
+        match subtag.len() {
+            // Region, e.g. `BR`.
+            2 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                normalized.push_str(&subtag.to_ascii_uppercase())
+            }
+            // Script, e.g. `Hant`.
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                normalized.push_str(&subtag[..1].to_ascii_uppercase());
+                normalized.push_str(&subtag[1..].to_ascii_lowercase());
+            }
+            _ => normalized.push_str(&subtag.to_ascii_lowercase()),
+        }
+    }
+    Ok(normalized)
+}
+
+/// The instruction added to a search's system instructions for `tag`, which
+/// Perplexica's API has no separate field for.
+pub fn language_instruction(tag: &str) -> String {
+    format!(
+        "Write the answer in the language identified by the BCP 47 tag '{}', whatever the language of the query or sources.",
+        tag
+    )
+}
+
+/// Appends the instruction for `tag` to `instructions`, if there are any.
+pub fn with_language(instructions: Option<String>, tag: &str) -> String {
+    let instruction = language_instruction(tag);
+    match instructions {
+        Some(instructions) => format!("{}\n\n{}", instructions.trim_end(), instruction),
+        None => instruction,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_language_tag() {
+        for (tag, expected) in [
+            ("en", "en"),
+            ("DE", "de"),
+            (" pt_br ", "pt-BR"),
+            ("zh-hant-tw", "zh-Hant-TW"),
+            ("es-419", "es-419"),
+            ("sl-rozaj-biske", "sl-rozaj-biske"),
+        ] {
+            assert_eq!(normalize_language_tag(tag).unwrap(), expected);
+        }
+        for tag in [
+            "",
+            "e",
+            "english",
+            "en-",
+            "en--US",
+            "en US",
+            "fr-toolongsubtag",
+            "1a",
+        ] {
+            let error = normalize_language_tag(tag).unwrap_err();
+            assert!(error.contains("is not a language tag"), "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn test_with_language_appends_to_instructions() {
+        assert_eq!(with_language(None, "de"), language_instruction("de"));
+        assert_eq!(
+            with_language(Some("Be brief.\n".to_string()), "fr-CA"),
+            format!("Be brief.\n\n{}", language_instruction("fr-CA"))
+        );
+    }
+}
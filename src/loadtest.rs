@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Upper bound on the searches one `perplexica_loadtest` call may run.
+pub const MAX_REQUESTS: usize = 50;
+/// Upper bound on the searches a load test keeps in flight at once.
+pub const MAX_CONCURRENCY: usize = 10;
+
+/// What one load test search did: how long it took, and the error kind if it
+/// failed.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub latency: Duration,
+    pub error_kind: Option<String>,
+}
+
+/// Summary of a load test run.
+#[derive(Debug, PartialEq)]
+pub struct LoadtestReport {
+    pub requests: usize,
+    pub concurrency: usize,
+    pub succeeded: usize,
+    pub elapsed: Duration,
+    /// `(percentile, latency)` pairs over all searches, failed ones included.
+    pub latency_percentiles: Vec<(u8, Duration)>,
+    /// Failed searches per error kind.
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl LoadtestReport {
+    pub fn new(concurrency: usize, elapsed: Duration, samples: &[Sample]) -> Self {
+        let mut latencies: Vec<Duration> = samples.iter().map(|sample| sample.latency).collect();
+        latencies.sort();
+
+        let mut errors = BTreeMap::new();
+        for kind in samples
+            .iter()
+            .filter_map(|sample| sample.error_kind.as_ref())
+        {
+            *errors.entry(kind.clone()).or_insert(0) += 1;
+        }
+
+        Self {
+            requests: samples.len(),
+            concurrency,
+            succeeded: samples.len() - errors.values().sum::<usize>(),
+            elapsed,
+            latency_percentiles: [50, 90, 95, 99]
+                .into_iter()
+                .filter_map(|p| percentile(&latencies, p).map(|latency| (p, latency)))
+                .collect(),
+            errors,
+        }
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.succeeded as f64 / self.requests as f64
+    }
+}
+
+/// Nearest-rank percentile of `sorted`: the smallest latency that at least `p`
+/// percent of the samples don't exceed. `None` for no samples.
+pub fn percentile(sorted: &[Duration], p: u8) -> Option<Duration> {
+    let rank = (usize::from(p) * sorted.len())
+        .div_ceil(100)
+        .clamp(1, sorted.len().max(1));
+    sorted.get(rank - 1).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(values: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        values.into_iter().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let latencies = ms(1..=100);
+
+        assert_eq!(percentile(&latencies, 50), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&latencies, 99), Some(Duration::from_millis(99)));
+        assert_eq!(
+            percentile(&latencies, 100),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(percentile(&latencies, 0), Some(Duration::from_millis(1)));
+
+        let latencies = ms([10, 20, 30, 40, 1000]);
+        assert_eq!(percentile(&latencies, 50), Some(Duration::from_millis(30)));
+        assert_eq!(
+            percentile(&latencies, 90),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn test_report_counts_successes_and_error_kinds() {
+        let sample = |latency_ms, error_kind: Option<&str>| Sample {
+            latency: Duration::from_millis(latency_ms),
+            error_kind: error_kind.map(String::from),
+        };
+        let samples = [
+            sample(40, None),
+            sample(10, None),
+            sample(30, Some("upstream_status")),
+            sample(20, Some("upstream_status")),
+            sample(50, Some("server_busy")),
+        ];
+
+        let report = LoadtestReport::new(2, Duration::from_millis(90), &samples);
+
+        assert_eq!(report.requests, 5);
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.success_rate(), 0.4);
+        assert_eq!(
+            report.latency_percentiles[0],
+            (50, Duration::from_millis(30))
+        );
+        assert_eq!(report.errors["upstream_status"], 2);
+        assert_eq!(report.errors["server_busy"], 1);
+    }
+}
@@ -0,0 +1,48 @@
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_FILTER: &str = "info";
+
+/// Installs a stderr subscriber. stdout carries the MCP JSON-RPC stream, so
+/// nothing may be logged there.
+pub fn init() {
+    let directive = filter_directive(|key| std::env::var(key).ok());
+    let filter = EnvFilter::try_new(&directive).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(false)
+        .init();
+}
+
+/// `PERPLEXICA_LOG` takes precedence over `RUST_LOG`; both fall back to `info`.
+fn filter_directive(lookup: impl Fn(&str) -> Option<String>) -> String {
+    ["PERPLEXICA_LOG", "RUST_LOG"]
+        .into_iter()
+        .filter_map(&lookup)
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::env;
+
+    #[test]
+    fn test_filter_directive_precedence() {
+        assert_eq!(filter_directive(env(&[])), "info");
+        assert_eq!(filter_directive(env(&[("RUST_LOG", "warn")])), "warn");
+        assert_eq!(
+            filter_directive(env(&[
+                ("RUST_LOG", "warn"),
+                ("PERPLEXICA_LOG", "perplexica_mcp=trace")
+            ])),
+            "perplexica_mcp=trace"
+        );
+        assert_eq!(
+            filter_directive(env(&[("PERPLEXICA_LOG", " "), ("RUST_LOG", "debug")])),
+            "debug"
+        );
+    }
+}
@@ -1,14 +1,142 @@
 use anyhow::Result;
-use perplexica_service::PerplexicaService;
+use clap::Parser;
+use cli::{Cli, Command};
+use perplexica_service::{PerplexicaService, parse_flag};
 use rmcp::{ServiceExt, transport::stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use transport::Transport;
 
+mod aliases;
+mod breaker;
+mod cache;
+mod check_config;
+mod cli;
+mod coalesce;
+mod config;
+mod dedupe;
+mod diff;
+mod disk_cache;
+mod envfile;
+mod error;
+mod fallback;
+mod format;
+mod history;
+mod html;
+mod instances;
+mod language;
+mod loadtest;
+mod logging;
+mod metrics;
+mod mock;
 mod perplexica_service;
+mod ping;
+mod pricing;
+mod profiles;
+mod progress;
+mod providers;
+mod ranking;
+mod resources;
+mod retry;
+mod sanitize;
+mod shutdown;
+mod stats;
+mod stream;
+mod suggest;
+#[cfg(test)]
+mod test_support;
+mod timeouts;
+mod transport;
+mod urls;
+mod verify;
+mod version;
+mod webhook;
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if cli.version {
+        println!("{}", version::build_info());
+        return Ok(());
+    }
+
+    // Before the runtime starts its threads, since this sets variables.
+    let env_file = envfile::load()?;
+
+    let lookup = |name: &str| std::env::var(name).ok();
+    if cli.check_config || parse_flag(lookup("PERPLEXICA_CHECK_CONFIG"), "PERPLEXICA_CHECK_CONFIG")?
+    {
+        let checks = check_config::check_config(lookup);
+        eprint!("{}", format::format_config_report(&checks));
+        std::process::exit(if checks.iter().all(|check| check.passed) {
+            0
+        } else {
+            1
+        });
+    }
+
+    run(env_file, cli.command)
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let service = PerplexicaService::new()?.serve(stdio()).await?;
+async fn run(
+    env_file: Option<(std::path::PathBuf, usize)>,
+    command: Option<Command>,
+) -> Result<()> {
+    logging::init();
+    if let Some((path, loaded)) = env_file {
+        tracing::info!(path = %path.display(), loaded, "read settings from env file");
+    }
+
+    if let Some(Command::Search(args)) = command {
+        let service = PerplexicaService::new()?;
+        return cli::search(&service, args, &mut std::io::stdout().lock()).await;
+    }
+
+    let transport = Transport::from_lookup(|name| std::env::var(name).ok())?;
+    let service = PerplexicaService::new()?;
+    service.check_on_start().await?;
+    let drain = service.drain();
+
+    if let Transport::Ws { addr, metrics } = transport {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tracing::info!(addr = %listener.local_addr()?, metrics, "serving MCP over WebSocket");
+        let drained = transport::serve_ws(listener, service, metrics, async {
+            shutdown::signal().await;
+            tracing::info!("shutdown signal received");
+        })
+        .await;
+        std::process::exit(if drained { 0 } else { 1 });
+    }
+
+    let running = service.serve(stdio()).await?;
+
+    // Cancelling the session cancels every request in it, so on a signal the
+    // drain runs first, while results can still be sent, and new searches
+    // are refused meanwhile.
+    let cancellation = running.cancellation_token();
+    let signal_drain = drain.clone();
+    let signalled = Arc::new(AtomicBool::new(false));
+    let on_signal = tokio::spawn({
+        let signalled = signalled.clone();
+        async move {
+            shutdown::signal().await;
+            tracing::info!("shutdown signal received");
+            signalled.store(true, Ordering::Relaxed);
+            let drained = signal_drain.run().await;
+            cancellation.cancel();
+            drained
+        }
+    });
 
-    service.waiting().await?;
+    running.waiting().await?;
+    let drained = if signalled.load(Ordering::Relaxed) {
+        on_signal.await.unwrap_or(false)
+    } else {
+        // The client closed stdin.
+        drain.run().await
+    };
 
-    Ok(())
+    // Stdin is read on a blocking thread that the runtime would wait on when
+    // dropped, so exit explicitly instead of returning from main.
+    std::process::exit(if drained { 0 } else { 1 });
 }
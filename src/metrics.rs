@@ -0,0 +1,95 @@
+use crate::stats::{LATENCY_BUCKETS, StatsSnapshot};
+use std::fmt::Write;
+
+/// The path scraped on the WebSocket listener when `PERPLEXICA_METRICS` is set.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Renders `stats` in the Prometheus text exposition format.
+pub fn render(stats: &StatsSnapshot) -> String {
+    let mut text = String::new();
+    // Writing into a String is infallible, so the fmt::Result is ignored.
+    let _ = writeln!(
+        text,
+        "# HELP perplexica_searches_total Searches run, including failed ones.\n\
+         # TYPE perplexica_searches_total counter\n\
+         perplexica_searches_total {}",
+        stats.searches
+    );
+
+    text.push_str(
+        "# HELP perplexica_search_errors_total Failed searches by error kind.\n\
+         # TYPE perplexica_search_errors_total counter\n",
+    );
+    for (kind, count) in &stats.failures {
+        let _ = writeln!(
+            text,
+            "perplexica_search_errors_total{{kind=\"{}\"}} {}",
+            escape_label(kind),
+            count
+        );
+    }
+
+    text.push_str(
+        "# HELP perplexica_search_duration_seconds How long searches took, including fallbacks.\n\
+         # TYPE perplexica_search_duration_seconds histogram\n",
+    );
+    let mut cumulative = 0;
+    for (index, bound) in LATENCY_BUCKETS.iter().enumerate() {
+        cumulative += stats.latency_buckets.get(index).copied().unwrap_or(0);
+        let _ = writeln!(
+            text,
+            "perplexica_search_duration_seconds_bucket{{le=\"{}\"}} {}",
+            bound, cumulative
+        );
+    }
+    let count: u64 = stats.latency_buckets.iter().sum();
+    let _ = writeln!(
+        text,
+        "perplexica_search_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+         perplexica_search_duration_seconds_sum {}\n\
+         perplexica_search_duration_seconds_count {}",
+        count,
+        stats.latency_sum.as_secs_f64(),
+        count
+    );
+
+    text
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_counters_and_histogram() {
+        let stats = Stats::default();
+        stats.record_search(None, Duration::from_millis(200));
+        stats.record_search(Some("upstream_status"), Duration::from_secs(3));
+
+        let text = render(&stats.snapshot());
+
+        for line in [
+            "# TYPE perplexica_searches_total counter",
+            "perplexica_searches_total 2",
+            "perplexica_search_errors_total{kind=\"upstream_status\"} 1",
+            "# TYPE perplexica_search_duration_seconds histogram",
+            "perplexica_search_duration_seconds_bucket{le=\"0.1\"} 0",
+            "perplexica_search_duration_seconds_bucket{le=\"0.25\"} 1",
+            "perplexica_search_duration_seconds_bucket{le=\"5\"} 2",
+            "perplexica_search_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "perplexica_search_duration_seconds_sum 3.2",
+            "perplexica_search_duration_seconds_count 2",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{}\n{}", line, text);
+        }
+    }
+}
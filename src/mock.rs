@@ -0,0 +1,142 @@
+use crate::perplexica_service::{PerplexicaSearchResponse, ProvidersResponse};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Placeholder in a canned search message that is replaced with the query.
+const QUERY_PLACEHOLDER: &str = "{query}";
+
+const DEFAULT_SEARCH: &str = r#"{
+    "message": "Mock answer for: {query}. This response comes from PERPLEXICA_MOCK, not a live Perplexica instance [1][2].",
+    "sources": [
+        {
+            "pageContent": "The first mock source.",
+            "metadata": { "title": "Mock Source One", "url": "https://example.com/mock-source-1" }
+        },
+        {
+            "pageContent": "The second mock source.",
+            "metadata": { "title": "Mock Source Two", "url": "https://example.com/mock-source-2" }
+        }
+    ]
+}"#;
+
+const DEFAULT_PROVIDERS: &str = r#"{
+    "providers": [
+        {
+            "id": "mock",
+            "name": "Mock Provider",
+            "chatModels": [{ "name": "Mock Chat", "key": "mock-chat" }],
+            "embeddingModels": [{ "name": "Mock Embedding", "key": "mock-embedding" }]
+        }
+    ]
+}"#;
+
+/// Canned responses served instead of calling Perplexica when `PERPLEXICA_MOCK`
+/// is set.
+///
+/// `PERPLEXICA_MOCK_FILE` may point to a JSON object with a `search` response
+/// and a `providers` list, each replacing the built-in one. `{query}` in the
+/// search message stands for the query being searched.
+#[derive(Debug, Deserialize)]
+pub struct MockData {
+    #[serde(default = "default_search")]
+    search: PerplexicaSearchResponse,
+    #[serde(default = "default_providers")]
+    providers: ProvidersResponse,
+}
+
+fn default_search() -> PerplexicaSearchResponse {
+    serde_json::from_str(DEFAULT_SEARCH).expect("built-in mock search response is valid")
+}
+
+fn default_providers() -> ProvidersResponse {
+    serde_json::from_str(DEFAULT_PROVIDERS).expect("built-in mock providers list is valid")
+}
+
+impl Default for MockData {
+    fn default() -> Self {
+        Self {
+            search: default_search(),
+            providers: default_providers(),
+        }
+    }
+}
+
+impl MockData {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read PERPLEXICA_MOCK_FILE {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        serde_json::from_str(&contents).map_err(|e| {
+            anyhow::anyhow!(
+                "PERPLEXICA_MOCK_FILE {} is not valid mock data: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    /// The canned search response, with the query echoed into its message.
+    pub fn search(&self, query: &str) -> PerplexicaSearchResponse {
+        PerplexicaSearchResponse {
+            message: self.search.message.replace(QUERY_PLACEHOLDER, query),
+            ..self.search.clone()
+        }
+    }
+
+    pub fn providers(&self) -> ProvidersResponse {
+        self.providers.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_data_echoes_query() {
+        let data = MockData::default();
+
+        let response = data.search("What is AI?");
+        assert!(
+            response
+                .message
+                .starts_with("Mock answer for: What is AI?.")
+        );
+        assert_eq!(response.sources.len(), 2);
+        assert_eq!(data.providers().providers[0].id, "mock");
+    }
+
+    #[test]
+    fn test_file_overrides_only_what_it_sets() {
+        let path =
+            std::env::temp_dir().join(format!("perplexica-mock-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"search": {"message": "Canned: {query}", "sources": []}}"#,
+        )
+        .unwrap();
+
+        let data = MockData::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.search("rust").message, "Canned: rust");
+        assert!(data.search("rust").sources.is_empty());
+        assert_eq!(data.providers().providers[0].id, "mock");
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_file() {
+        let path =
+            std::env::temp_dir().join(format!("perplexica-mock-bad-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"search": {"sources": []}}"#).unwrap();
+
+        let error = MockData::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(error.to_string().contains("is not valid mock data"));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::format::{join_or_none, model_keys};
+use crate::perplexica_service::{Model, ProvidersResponse};
+
+/// The one-word query `perplexica_ping_providers` searches for.
+pub const PING_QUERY: &str = "ping";
+
+/// One line of the `perplexica_ping_providers` checklist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub label: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn pass(label: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            passed: true,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn fail(label: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            passed: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The provider and model keys a search falls back to, after aliases.
+#[derive(Debug)]
+pub struct Defaults<'a> {
+    pub provider_id: &'a str,
+    pub chat_model_key: &'a str,
+    pub embedding_provider_id: &'a str,
+    pub embedding_model_key: &'a str,
+}
+
+/// Checks that each default names a provider and model Perplexica lists. The
+/// embedding provider only gets its own line when it differs from the chat
+/// model's.
+pub fn check_defaults(providers: &ProvidersResponse, defaults: &Defaults) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let check_provider = |provider_id: &str, checks: &mut Vec<Check>| {
+        let label = format!("Provider `{}`", provider_id);
+        let provider = providers.providers.iter().find(|p| p.id == provider_id);
+        checks.push(match provider {
+            Some(provider) => Check::pass(label, format!("found ({})", provider.name)),
+            None => {
+                let available: Vec<_> = providers
+                    .providers
+                    .iter()
+                    .map(|p| format!("`{}`", p.id))
+                    .collect();
+                Check::fail(
+                    label,
+                    format!(
+                        "not found. Available providers: {}",
+                        join_or_none(&available)
+                    ),
+                )
+            }
+        });
+        provider
+    };
+
+    let chat_provider = check_provider(defaults.provider_id, &mut checks);
+    checks.push(check_model(
+        "Chat model",
+        "chat models",
+        defaults.chat_model_key,
+        chat_provider.map(|provider| provider.chat_models.as_slice()),
+    ));
+
+    let embedding_provider = if defaults.embedding_provider_id == defaults.provider_id {
+        chat_provider
+    } else {
+        check_provider(defaults.embedding_provider_id, &mut checks)
+    };
+    checks.push(check_model(
+        "Embedding model",
+        "embedding models",
+        defaults.embedding_model_key,
+        embedding_provider.map(|provider| provider.embedding_models.as_slice()),
+    ));
+    checks
+}
+
+/// Checks `key` against its provider's `models`, or fails outright when the
+/// provider itself is missing.
+fn check_model(label: &str, plural: &str, key: &str, models: Option<&[Model]>) -> Check {
+    let label = format!("{} `{}`", label, key);
+    match models {
+        None => Check::fail(label, "its provider was not found"),
+        Some(models) if models.iter().any(|model| model.key == key) => Check::pass(label, "found"),
+        Some(models) => Check::fail(
+            label,
+            format!(
+                "not found. Available {}: {}",
+                plural,
+                join_or_none(&model_keys(models))
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::Provider;
+
+    fn providers() -> ProvidersResponse {
+        let model = |key: &str| Model {
+            name: key.to_string(),
+            key: key.to_string(),
+        };
+        ProvidersResponse {
+            providers: vec![
+                Provider {
+                    id: "openai".to_string(),
+                    name: "OpenAI".to_string(),
+                    chat_models: vec![model("gpt-4o")],
+                    embedding_models: vec![model("text-embedding-3-small")],
+                },
+                Provider {
+                    id: "ollama".to_string(),
+                    name: "Ollama".to_string(),
+                    chat_models: vec![],
+                    embedding_models: vec![model("nomic-embed-text")],
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_defaults_all_found() {
+        let checks = check_defaults(
+            &providers(),
+            &Defaults {
+                provider_id: "openai",
+                chat_model_key: "gpt-4o",
+                embedding_provider_id: "ollama",
+                embedding_model_key: "nomic-embed-text",
+            },
+        );
+
+        let labels: Vec<_> = checks.iter().map(|check| check.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "Provider `openai`",
+                "Chat model `gpt-4o`",
+                "Provider `ollama`",
+                "Embedding model `nomic-embed-text`",
+            ]
+        );
+        assert!(checks.iter().all(|check| check.passed), "{:?}", checks);
+    }
+
+    #[test]
+    fn test_check_defaults_reports_what_is_missing() {
+        let checks = check_defaults(
+            &providers(),
+            &Defaults {
+                provider_id: "openai",
+                chat_model_key: "gpt-5",
+                embedding_provider_id: "openai",
+                embedding_model_key: "text-embedding-3-small",
+            },
+        );
+        assert_eq!(checks.len(), 3);
+        assert_eq!(
+            checks[1],
+            Check::fail(
+                "Chat model `gpt-5`",
+                "not found. Available chat models: `gpt-4o`"
+            )
+        );
+        assert!(checks[2].passed);
+
+        let checks = check_defaults(
+            &providers(),
+            &Defaults {
+                provider_id: "anthropic",
+                chat_model_key: "claude",
+                embedding_provider_id: "anthropic",
+                embedding_model_key: "voyage",
+            },
+        );
+        assert_eq!(
+            checks[0].detail,
+            "not found. Available providers: `openai`, `ollama`"
+        );
+        assert_eq!(checks[1].detail, "its provider was not found");
+        assert!(checks.iter().all(|check| !check.passed));
+    }
+}
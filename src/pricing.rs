@@ -0,0 +1,189 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Rough characters per token for English text.
+pub const CHARS_PER_TOKEN: usize = 4;
+/// Tokens of retrieved source content assumed to reach the chat model, and to
+/// be embedded for reranking.
+pub const ASSUMED_CONTEXT_TOKENS: usize = 4_000;
+/// Tokens assumed for the generated answer.
+pub const ASSUMED_OUTPUT_TOKENS: usize = 500;
+
+/// Prices for one model, in the pricing file's currency per 1,000 tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_1k_tokens: f64,
+    #[serde(default)]
+    pub output_per_1k_tokens: f64,
+}
+
+/// Per-model prices loaded from `PERPLEXICA_PRICING_FILE`: a JSON object keyed
+/// by `provider_id/model_key` or by bare model key, the former taking priority.
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable(HashMap<String, ModelPrice>);
+
+impl PricingTable {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read PERPLEXICA_PRICING_FILE {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::parse(&contents).map_err(|e| {
+            anyhow::anyhow!(
+                "PERPLEXICA_PRICING_FILE {} is not a valid pricing table: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json).map(Self)
+    }
+
+    fn price(&self, provider_id: &str, key: &str) -> Option<ModelPrice> {
+        self.0
+            .get(&format!("{}/{}", provider_id, key))
+            .or_else(|| self.0.get(key))
+            .copied()
+    }
+}
+
+/// The text a search sends to Perplexica, and the models it uses.
+#[derive(Debug)]
+pub struct EstimateInput<'a> {
+    /// Query, history messages and system instructions.
+    pub prompt: Vec<&'a str>,
+    pub query: &'a str,
+    pub chat_model: (&'a str, &'a str),
+    pub embedding_model: (&'a str, &'a str),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ModelCost {
+    pub provider_id: String,
+    pub key: String,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// `None` when the pricing table has no entry for the model.
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CostEstimate {
+    pub prompt_tokens: usize,
+    pub chat: ModelCost,
+    pub embedding: ModelCost,
+}
+
+impl CostEstimate {
+    /// Sum of the priced models; unpriced ones count as zero.
+    pub fn total(&self) -> f64 {
+        self.chat.cost.unwrap_or(0.0) + self.embedding.cost.unwrap_or(0.0)
+    }
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimates a search's cost: the chat model reads the prompt plus the
+/// retrieved context and writes an answer, and the embedding model embeds the
+/// query and the context.
+pub fn estimate_cost(table: &PricingTable, input: &EstimateInput) -> CostEstimate {
+    let prompt_tokens: usize = input.prompt.iter().map(|text| estimate_tokens(text)).sum();
+
+    let model_cost = |(provider_id, key): (&str, &str), input_tokens, output_tokens| {
+        let cost = table.price(provider_id, key).map(|price| {
+            (input_tokens as f64 * price.input_per_1k_tokens
+                + output_tokens as f64 * price.output_per_1k_tokens)
+                / 1000.0
+        });
+        ModelCost {
+            provider_id: provider_id.to_string(),
+            key: key.to_string(),
+            input_tokens,
+            output_tokens,
+            cost,
+        }
+    };
+
+    CostEstimate {
+        prompt_tokens,
+        chat: model_cost(
+            input.chat_model,
+            prompt_tokens + ASSUMED_CONTEXT_TOKENS,
+            ASSUMED_OUTPUT_TOKENS,
+        ),
+        embedding: model_cost(
+            input.embedding_model,
+            estimate_tokens(input.query) + ASSUMED_CONTEXT_TOKENS,
+            0,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICING: &str = r#"{
+        "gpt-4o-mini": { "input_per_1k_tokens": 0.5, "output_per_1k_tokens": 2.0 },
+        "azure/gpt-4o-mini": { "input_per_1k_tokens": 1.0, "output_per_1k_tokens": 4.0 },
+        "text-embedding-3-large": { "input_per_1k_tokens": 0.1 }
+    }"#;
+
+    #[test]
+    fn test_estimate_cost() {
+        let table = PricingTable::parse(PRICING).unwrap();
+        // 8 + 12 characters: 2 + 3 tokens.
+        let input = EstimateInput {
+            prompt: vec!["12345678", "hello world!"],
+            query: "12345678",
+            chat_model: ("openai", "gpt-4o-mini"),
+            embedding_model: ("openai", "text-embedding-3-large"),
+        };
+
+        let estimate = estimate_cost(&table, &input);
+
+        assert_eq!(estimate.prompt_tokens, 5);
+        assert_eq!(estimate.chat.input_tokens, 4_005);
+        assert_eq!(estimate.chat.output_tokens, 500);
+        let chat_cost = (4_005.0 * 0.5 + 500.0 * 2.0) / 1000.0;
+        assert_eq!(estimate.chat.cost, Some(chat_cost));
+        assert_eq!(estimate.embedding.input_tokens, 4_002);
+        let embedding_cost = 4_002.0 * 0.1 / 1000.0;
+        assert_eq!(estimate.embedding.cost, Some(embedding_cost));
+        assert_eq!(estimate.total(), chat_cost + embedding_cost);
+    }
+
+    #[test]
+    fn test_provider_specific_price_wins_and_missing_is_unpriced() {
+        let table = PricingTable::parse(PRICING).unwrap();
+        let input = EstimateInput {
+            prompt: vec![""],
+            query: "",
+            chat_model: ("azure", "gpt-4o-mini"),
+            embedding_model: ("local", "nomic-embed"),
+        };
+
+        let estimate = estimate_cost(&table, &input);
+
+        assert_eq!(
+            estimate.chat.cost,
+            Some((4_000.0 * 1.0 + 500.0 * 4.0) / 1000.0)
+        );
+        assert_eq!(estimate.embedding.cost, None);
+        assert_eq!(estimate.total(), estimate.chat.cost.unwrap());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_table() {
+        assert!(PricingTable::parse(r#"{"gpt-4": {"output_per_1k_tokens": 1}}"#).is_err());
+        assert!(PricingTable::parse("[]").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A named bundle of search settings from `PERPLEXICA_PROFILES_FILE`. Every
+/// field is optional; what a profile leaves out falls back to the usual
+/// defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Perplexica instance to search instead of `PERPLEXICA_API_URL`.
+    pub base_url: Option<String>,
+    pub provider_id: Option<String>,
+    pub chat_model_key: Option<String>,
+    pub embedding_model_key: Option<String>,
+    pub focus_mode: Option<String>,
+}
+
+/// Profiles by name, loaded from the JSON object at `PERPLEXICA_PROFILES_FILE`:
+///
+/// ```json
+/// { "local": { "base_url": "http://localhost:3000", "provider_id": "ollama" } }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Profiles(BTreeMap<String, Profile>);
+
+impl Profiles {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read PERPLEXICA_PROFILES_FILE {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        let profiles: BTreeMap<String, Profile> = serde_json::from_str(&contents).map_err(|e| {
+            anyhow::anyhow!(
+                "PERPLEXICA_PROFILES_FILE {} is not valid profiles JSON: {}",
+                path.display(),
+                e
+            )
+        })?;
+        if let Some(name) = profiles.keys().find(|name| name.trim().is_empty()) {
+            anyhow::bail!(
+                "PERPLEXICA_PROFILES_FILE {} has a profile with an empty name '{}'",
+                path.display(),
+                name
+            );
+        }
+        Ok(Self(profiles))
+    }
+
+    /// Looks up a profile, listing the configured names when there is none
+    /// by that name.
+    pub fn get(&self, name: &str) -> Result<&Profile, String> {
+        self.0.get(name).ok_or_else(|| {
+            if self.0.is_empty() {
+                format!(
+                    "Unknown profile '{}': no profiles are configured. Set PERPLEXICA_PROFILES_FILE to define them.",
+                    name
+                )
+            } else {
+                format!(
+                    "Unknown profile '{}'. Available profiles: {}",
+                    name,
+                    self.names().join(", ")
+                )
+            }
+        })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Profile)> {
+        self.0
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> anyhow::Result<Profiles> {
+        let path = std::env::temp_dir().join(format!(
+            "perplexica-profiles-{}-{}.json",
+            std::process::id(),
+            contents.len()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let profiles = Profiles::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        profiles
+    }
+
+    #[test]
+    fn test_load_profiles() {
+        let profiles = load(
+            r#"{
+                "local": {
+                    "base_url": "http://localhost:3000",
+                    "provider_id": "ollama",
+                    "chat_model_key": "llama3:8b",
+                    "embedding_model_key": "nomic-embed-text",
+                    "focus_mode": "academicSearch"
+                },
+                "cloud": { "provider_id": "openai" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(profiles.names(), ["cloud", "local"]);
+        let local = profiles.get("local").unwrap();
+        assert_eq!(local.base_url.as_deref(), Some("http://localhost:3000"));
+        assert_eq!(local.chat_model_key.as_deref(), Some("llama3:8b"));
+        assert_eq!(local.focus_mode.as_deref(), Some("academicSearch"));
+        assert_eq!(
+            profiles.get("cloud").unwrap(),
+            &Profile {
+                provider_id: Some("openai".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_available_names() {
+        let profiles = load(r#"{"b": {}, "a": {}}"#).unwrap();
+        assert_eq!(
+            profiles.get("c").unwrap_err(),
+            "Unknown profile 'c'. Available profiles: a, b"
+        );
+
+        let error = Profiles::default().get("c").unwrap_err();
+        assert!(error.contains("no profiles are configured"), "{}", error);
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_profiles() {
+        let error = load(r#"{"local": {"chat_model": "gpt-4o"}}"#).unwrap_err();
+        assert!(
+            error.to_string().contains("is not valid profiles JSON"),
+            "{}",
+            error
+        );
+
+        let error = load(r#"{" ": {}}"#).unwrap_err();
+        assert!(error.to_string().contains("empty name"), "{}", error);
+
+        let error = Profiles::load(Path::new("/nonexistent/profiles.json")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Failed to read PERPLEXICA_PROFILES_FILE")
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use rmcp::{
+    Peer, RoleServer,
+    model::{ProgressNotificationParam, ProgressToken},
+};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How often a long call reports that it is still working.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Runs `work`, sending a progress notification for `token` when it starts and
+/// every `interval` until it finishes, so the client can show that a slow
+/// search is still running. The total is unknown, so `progress` counts the
+/// notifications sent. Without a token, which the client sends only when it
+/// tracks progress, `work` simply runs.
+pub async fn with_heartbeat<F: Future>(
+    work: F,
+    peer: &Peer<RoleServer>,
+    token: Option<ProgressToken>,
+    interval: Duration,
+) -> F::Output {
+    let Some(token) = token else {
+        return work.await;
+    };
+
+    let started = Instant::now();
+    let mut ticks = tokio::time::interval(interval);
+    tokio::pin!(work);
+    let mut beats = 0u32;
+    loop {
+        tokio::select! {
+            output = &mut work => return output,
+            _ = ticks.tick() => {
+                let message = match beats {
+                    0 => "Searching".to_string(),
+                    _ => format!("Still searching ({}s)", started.elapsed().as_secs()),
+                };
+                beats += 1;
+                // A client that has gone away just misses the heartbeat.
+                let _ = peer
+                    .notify_progress(ProgressNotificationParam {
+                        progress_token: token.clone(),
+                        progress: f64::from(beats),
+                        total: None,
+                        message: Some(message),
+                    })
+                    .await;
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::perplexica_service::{Model, Provider, ProvidersResponse};
+use serde_json::{Map, Value};
+
+/// Reads Perplexica's providers list without insisting on an exact shape, so a
+/// renamed key or an unexpected entry costs that entry rather than the whole
+/// list. Entries that can't be read are skipped and described in
+/// [`ProvidersResponse::skipped`]; only a body with no providers list at all
+/// is an error.
+///
+/// The list may be the `providers` field or the body itself, as an array or
+/// as an object keyed by provider ID. Model lists take the same forms, and a
+/// model may also be a bare key. Numeric IDs and keys are read as strings,
+/// and a missing name falls back to the ID or key.
+pub fn extract_providers(body: &Value) -> Result<ProvidersResponse, String> {
+    let list = match body {
+        Value::Object(object) => object
+            .get("providers")
+            .ok_or_else(|| "no 'providers' field".to_string())?,
+        Value::Array(_) => body,
+        _ => return Err("expected a JSON object or array".to_string()),
+    };
+    let entries = entries(list).ok_or_else(|| "'providers' is not a list".to_string())?;
+
+    let mut skipped = Vec::new();
+    let providers = entries
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (map_key, entry))| {
+            let provider = extract_provider(entry, map_key, &mut skipped);
+            if provider.is_none() {
+                skipped.push(format!("provider {} has no id", index + 1));
+            }
+            provider
+        })
+        .collect();
+
+    Ok(ProvidersResponse { providers, skipped })
+}
+
+/// The entries of an array, or of an object along with their keys.
+fn entries(list: &Value) -> Option<Vec<(Option<&str>, &Value)>> {
+    match list {
+        Value::Array(items) => Some(items.iter().map(|item| (None, item)).collect()),
+        Value::Object(map) => Some(
+            map.iter()
+                .map(|(key, item)| (Some(key.as_str()), item))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn extract_provider(
+    entry: &Value,
+    map_key: Option<&str>,
+    skipped: &mut Vec<String>,
+) -> Option<Provider> {
+    let object = entry.as_object()?;
+    let id = text_field(object, &["id", "providerId"]).or_else(|| map_key.map(String::from))?;
+    let name = text_field(object, &["name", "displayName"]).unwrap_or_else(|| id.clone());
+    let mut models =
+        |fields: &[&str], label: &str| extract_models(object, fields, label, &id, skipped);
+    let chat_models = models(&["chatModels", "chat_models"], "chat");
+    let embedding_models = models(&["embeddingModels", "embedding_models"], "embedding");
+
+    Some(Provider {
+        id,
+        name,
+        chat_models,
+        embedding_models,
+    })
+}
+
+fn extract_models(
+    provider: &Map<String, Value>,
+    fields: &[&str],
+    label: &str,
+    provider_id: &str,
+    skipped: &mut Vec<String>,
+) -> Vec<Model> {
+    let Some(list) = fields.iter().find_map(|field| provider.get(*field)) else {
+        return Vec::new();
+    };
+    let Some(entries) = entries(list) else {
+        skipped.push(format!(
+            "{} models of provider '{}' are not a list",
+            label, provider_id
+        ));
+        return Vec::new();
+    };
+
+    entries
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (map_key, entry))| {
+            let model = match entry {
+                Value::String(key) => Some(Model {
+                    name: key.clone(),
+                    key: key.clone(),
+                }),
+                Value::Object(object) => text_field(object, &["key", "id"])
+                    .or_else(|| map_key.map(String::from))
+                    .or_else(|| text_field(object, &["name"]))
+                    .map(|key| Model {
+                        name: text_field(object, &["name", "displayName"])
+                            .unwrap_or_else(|| key.clone()),
+                        key,
+                    }),
+                _ => None,
+            };
+            if model.is_none() {
+                skipped.push(format!(
+                    "{} model {} of provider '{}' has no key",
+                    label,
+                    index + 1,
+                    provider_id
+                ));
+            }
+            model
+        })
+        .collect()
+}
+
+/// The first of `fields` holding a non-empty string or a number.
+fn text_field(object: &Map<String, Value>, fields: &[&str]) -> Option<String> {
+    fields.iter().find_map(|field| match object.get(*field)? {
+        Value::String(text) if !text.trim().is_empty() => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expected_shape_is_read_as_is() {
+        let providers = extract_providers(&json!({
+            "providers": [{
+                "id": "openai",
+                "name": "OpenAI",
+                "chatModels": [{ "name": "GPT-4o", "key": "gpt-4o" }],
+                "embeddingModels": [{ "name": "Small", "key": "text-embedding-3-small" }]
+            }]
+        }))
+        .unwrap();
+
+        assert!(providers.skipped.is_empty());
+        assert_eq!(providers.providers.len(), 1);
+        let provider = &providers.providers[0];
+        assert_eq!(
+            (provider.id.as_str(), provider.name.as_str()),
+            ("openai", "OpenAI")
+        );
+        assert_eq!(provider.chat_models[0].key, "gpt-4o");
+        assert_eq!(provider.embedding_models[0].name, "Small");
+    }
+
+    #[test]
+    fn test_slightly_off_shape_keeps_what_it_can() {
+        let providers = extract_providers(&json!({
+            "providers": [
+                {
+                    "id": 7,
+                    "chatModels": ["llama3:8b", { "id": "qwen", "displayName": "Qwen" }, 42],
+                    "embeddingModels": { "nomic": { "name": "Nomic Embed" } },
+                    "capabilities": { "vision": true }
+                },
+                { "name": "Nameless, no id" },
+                { "id": "broken", "name": "Broken", "chatModels": "gpt-4o" }
+            ],
+            "version": 2
+        }))
+        .unwrap();
+
+        let ids: Vec<_> = providers.providers.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["7", "broken"]);
+        let first = &providers.providers[0];
+        assert_eq!(first.name, "7");
+        let chat: Vec<_> = first
+            .chat_models
+            .iter()
+            .map(|m| (m.key.as_str(), m.name.as_str()))
+            .collect();
+        assert_eq!(chat, [("llama3:8b", "llama3:8b"), ("qwen", "Qwen")]);
+        assert_eq!(first.embedding_models[0].key, "nomic");
+        assert_eq!(first.embedding_models[0].name, "Nomic Embed");
+        assert!(providers.providers[1].chat_models.is_empty());
+
+        assert_eq!(
+            providers.skipped,
+            [
+                "chat model 3 of provider '7' has no key",
+                "provider 2 has no id",
+                "chat models of provider 'broken' are not a list",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bare_list_or_map_of_providers() {
+        let list = extract_providers(&json!([{ "id": "a", "name": "A" }])).unwrap();
+        assert_eq!(list.providers[0].id, "a");
+
+        let map =
+            extract_providers(&json!({ "providers": { "ollama": { "name": "Ollama" } } })).unwrap();
+        assert_eq!(map.providers[0].id, "ollama");
+        assert_eq!(map.providers[0].name, "Ollama");
+    }
+
+    #[test]
+    fn test_no_providers_list_is_an_error() {
+        for body in [
+            json!({ "models": [] }),
+            json!({ "providers": 3 }),
+            json!("ok"),
+        ] {
+            assert!(extract_providers(&body).is_err(), "{}", body);
+        }
+    }
+}
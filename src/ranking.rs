@@ -0,0 +1,244 @@
+use crate::perplexica_service::Source;
+
+/// Default weight given to recency when ranking sources.
+pub const DEFAULT_RECENCY_WEIGHT: f64 = 0.3;
+
+/// Returns source indices ordered by a combined relevance/recency score.
+///
+/// Scores and publication dates are each min-max normalized to `[0, 1]` across
+/// the sources that carry them, and every source is ranked by
+///
+/// ```text
+/// combined = (1 - recency_weight) * relevance + recency_weight * recency
+/// ```
+///
+/// where a missing score or date contributes `0`. When no source has a score
+/// the original order is kept; when no source has a parseable date the ranking
+/// is by relevance alone. Ties keep their original relative order.
+pub fn rank_sources(sources: &[Source], recency_weight: f64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sources.len()).collect();
+
+    let scores: Vec<Option<f64>> = sources.iter().map(|s| s.metadata.score).collect();
+    if scores.iter().all(Option::is_none) {
+        return order;
+    }
+
+    let dates: Vec<Option<f64>> = sources
+        .iter()
+        .map(|s| {
+            s.metadata
+                .published_date
+                .as_deref()
+                .and_then(parse_date_days)
+                .map(|days| days as f64)
+        })
+        .collect();
+
+    let relevance = normalize(&scores);
+    let recency_weight = if dates.iter().all(Option::is_none) {
+        0.0
+    } else {
+        recency_weight
+    };
+    let recency = normalize(&dates);
+
+    let combined: Vec<f64> = relevance
+        .iter()
+        .zip(&recency)
+        .map(|(rel, rec)| (1.0 - recency_weight) * rel + recency_weight * rec)
+        .collect();
+
+    order.sort_by(|&a, &b| combined[b].total_cmp(&combined[a]));
+    order
+}
+
+/// Returns source indices ordered by descending score, with unscored sources
+/// last. Ties keep their original relative order.
+pub fn sort_by_score(sources: &[Source]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sources.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (sources[a].metadata.score, sources[b].metadata.score);
+        match (a, b) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    });
+    order
+}
+
+/// Counts the `[n]` citation markers in `message` for each of `source_count`
+/// sources. Markers may hold a comma-separated list such as `[1, 3]`; numbers
+/// outside `1..=source_count` are ignored.
+pub fn count_citations(message: &str, source_count: usize) -> Vec<usize> {
+    let mut counts = vec![0; source_count];
+
+    for segment in message.split('[').skip(1) {
+        let Some((marker, _)) = segment.split_once(']') else {
+            continue;
+        };
+        let numbers: Option<Vec<usize>> = marker
+            .split(',')
+            .map(|part| part.trim().parse().ok())
+            .collect();
+
+        for number in numbers.unwrap_or_default() {
+            if (1..=source_count).contains(&number) {
+                counts[number - 1] += 1;
+            }
+        }
+    }
+
+    counts
+}
+
+/// Returns the index and citation count of the most-cited source, preferring the
+/// earliest source on ties. Returns `None` when nothing is cited.
+pub fn most_cited_source(message: &str, source_count: usize) -> Option<(usize, usize)> {
+    count_citations(message, source_count)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .fold(None, |best, (index, count)| match best {
+            Some((_, best_count)) if best_count >= count => best,
+            _ => Some((index, count)),
+        })
+}
+
+/// Min-max normalizes the present values to `[0, 1]`; missing values map to `0`.
+/// If all present values are equal they normalize to `1`.
+fn normalize(values: &[Option<f64>]) -> Vec<f64> {
+    let present = values.iter().flatten();
+    let min = present.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = present.copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+
+    values
+        .iter()
+        .map(|value| match value {
+            Some(value) if range > 0.0 => (value - min) / range,
+            Some(_) => 1.0,
+            None => 0.0,
+        })
+        .collect()
+}
+
+/// Parses the `YYYY-MM-DD` prefix of a date string (which also covers RFC 3339
+/// timestamps) into days since the Unix epoch.
+fn parse_date_days(date: &str) -> Option<i64> {
+    let date = date.trim();
+    let year: i64 = date.get(0..4)?.parse().ok()?;
+    let month: i64 = date.get(5..7)?.parse().ok()?;
+    let day: i64 = date.get(8..10)?.parse().ok()?;
+
+    if date.get(4..5)? != "-" || date.get(7..8)? != "-" {
+        return None;
+    }
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days-from-civil conversion for the proleptic Gregorian calendar.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::SourceMetadata;
+
+    fn source(score: Option<f64>, published_date: Option<&str>) -> Source {
+        Source {
+            page_content: String::new(),
+            metadata: SourceMetadata {
+                title: String::new(),
+                url: String::new(),
+                score,
+                published_date: published_date.map(String::from),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_count_citations() {
+        let counts = count_citations("A [1][2]. B [2, 3]. C [3][9] [x] [2", 3);
+
+        assert_eq!(counts, vec![1, 2, 2]);
+        assert_eq!(count_citations("No citations here.", 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_most_cited_source() {
+        assert_eq!(most_cited_source("[2] [2] [1] [3]", 3), Some((1, 2)));
+        // Ties go to the source listed first.
+        assert_eq!(most_cited_source("[3] [2] [3] [2]", 3), Some((1, 2)));
+        assert_eq!(most_cited_source("[1]", 3), Some((0, 1)));
+        assert_eq!(most_cited_source("Nothing cited [7].", 3), None);
+    }
+
+    #[test]
+    fn test_parse_date_days() {
+        assert_eq!(parse_date_days("1970-01-01"), Some(0));
+        assert_eq!(parse_date_days("2000-03-01T12:00:00Z"), Some(11_017));
+        assert_eq!(parse_date_days("March 2024"), None);
+        assert_eq!(parse_date_days("2024-13-01"), None);
+    }
+
+    #[test]
+    fn test_rank_sources_combines_score_and_recency() {
+        let sources = vec![
+            source(Some(0.9), Some("2015-01-01")),
+            source(Some(0.8), Some("2024-06-01")),
+            source(Some(0.1), Some("2010-01-01")),
+        ];
+
+        // Relevance alone keeps the highest score first.
+        assert_eq!(rank_sources(&sources, 0.0), vec![0, 1, 2]);
+        // Weighting recency lifts the recent, nearly-as-relevant source.
+        assert_eq!(rank_sources(&sources, 0.5), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_rank_sources_with_mixed_metadata() {
+        let sources = vec![
+            source(None, Some("2024-01-01")),
+            source(Some(0.2), None),
+            source(Some(0.6), Some("not a date")),
+            source(Some(0.4), Some("2023-01-01")),
+        ];
+
+        // Missing scores and dates count as zero in the combined score.
+        assert_eq!(rank_sources(&sources, 0.4), vec![2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn test_sort_by_score() {
+        let sources = vec![
+            source(None, None),
+            source(Some(0.4), None),
+            source(Some(0.9), None),
+            source(None, None),
+            source(Some(0.4), None),
+        ];
+
+        assert_eq!(sort_by_score(&sources), vec![2, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn test_rank_sources_fallbacks() {
+        let no_scores = vec![
+            source(None, Some("2020-01-01")),
+            source(None, Some("2024-01-01")),
+        ];
+        assert_eq!(rank_sources(&no_scores, 0.5), vec![0, 1]);
+
+        let no_dates = vec![source(Some(0.1), None), source(Some(0.9), None)];
+        assert_eq!(rank_sources(&no_dates, 0.9), vec![1, 0]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::error::ServiceError;
+use crate::perplexica_service::ProvidersResponse;
+use rmcp::model::{
+    AnnotateAble, RawResource, RawResourceTemplate, Resource, ResourceContents, ResourceTemplate,
+};
+
+pub const PROVIDERS_URI: &str = "perplexica://providers";
+pub const PROVIDER_URI_TEMPLATE: &str = "perplexica://providers/{id}";
+
+const JSON_MIME_TYPE: &str = "application/json";
+
+fn provider_uri(id: &str) -> String {
+    format!("{}/{}", PROVIDERS_URI, id)
+}
+
+/// Lists the full providers resource followed by one resource per provider.
+pub fn list(providers: &ProvidersResponse) -> Vec<Resource> {
+    let mut all = RawResource::new(PROVIDERS_URI, "providers");
+    all.description = Some("All Perplexica providers and their models".to_string());
+    all.mime_type = Some(JSON_MIME_TYPE.to_string());
+
+    let per_provider = providers.providers.iter().map(|provider| {
+        let mut resource = RawResource::new(provider_uri(&provider.id), &provider.name);
+        resource.description = Some(format!(
+            "Chat and embedding models for the {} provider",
+            provider.name
+        ));
+        resource.mime_type = Some(JSON_MIME_TYPE.to_string());
+        resource.no_annotation()
+    });
+
+    std::iter::once(all.no_annotation())
+        .chain(per_provider)
+        .collect()
+}
+
+pub fn templates() -> Vec<ResourceTemplate> {
+    vec![
+        RawResourceTemplate {
+            uri_template: PROVIDER_URI_TEMPLATE.to_string(),
+            name: "provider".to_string(),
+            title: None,
+            description: Some("A single Perplexica provider and its models".to_string()),
+            mime_type: Some(JSON_MIME_TYPE.to_string()),
+        }
+        .no_annotation(),
+    ]
+}
+
+/// Renders the resource at `uri` as pretty-printed JSON.
+pub fn read(providers: &ProvidersResponse, uri: &str) -> Result<ResourceContents, ServiceError> {
+    let json = if uri == PROVIDERS_URI {
+        serde_json::to_string_pretty(providers)
+    } else {
+        let provider = uri
+            .strip_prefix(PROVIDERS_URI)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .and_then(|id| providers.providers.iter().find(|p| p.id == id))
+            .ok_or_else(|| ServiceError::ResourceNotFound {
+                uri: uri.to_string(),
+            })?;
+        serde_json::to_string_pretty(provider)
+    }
+    .map_err(|e| ServiceError::Internal(format!("Failed to serialize providers data: {}", e)))?;
+
+    Ok(ResourceContents::TextResourceContents {
+        uri: uri.to_string(),
+        mime_type: Some(JSON_MIME_TYPE.to_string()),
+        text: json,
+        meta: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perplexica_service::Provider;
+
+    fn providers() -> ProvidersResponse {
+        ProvidersResponse {
+            providers: vec![Provider {
+                id: "openai".to_string(),
+                name: "OpenAI".to_string(),
+                chat_models: Vec::new(),
+                embedding_models: Vec::new(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_read_unknown_resource() {
+        for uri in [
+            "perplexica://providers/missing",
+            "perplexica://providersopenai",
+            "file:///etc/hosts",
+        ] {
+            let error = read(&providers(), uri).unwrap_err();
+            assert_eq!(error.kind(), "resource_not_found", "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 0;
+pub const DEFAULT_MAX_RETRIES_CEILING: u32 = 5;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 250;
+
+/// Upper bound on any single backoff delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How much randomness is applied to each backoff delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JitterStrategy {
+    /// Uniform in `[0, delay)`. Spreads concurrent retries out the most.
+    #[default]
+    Full,
+    /// Uniform in `[delay / 2, delay)`. Keeps a minimum wait while still spreading retries.
+    Equal,
+    /// Exactly `delay`. Deterministic, which suits tests, but concurrent clients retry in lockstep.
+    None,
+}
+
+impl JitterStrategy {
+    pub const VALID_VALUES: &'static [&'static str] = &["full", "equal", "none"];
+}
+
+impl FromStr for JitterStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "equal" => Ok(Self::Equal),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "PERPLEXICA_BACKOFF_JITTER must be one of {}, got '{}'",
+                Self::VALID_VALUES.join(", "),
+                value
+            )),
+        }
+    }
+}
+
+/// How transient upstream failures are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries used when a request doesn't ask for a specific count.
+    pub max_retries: u32,
+    /// Hard cap on retries, including per-request overrides.
+    pub max_retries_ceiling: u32,
+    /// Delay before the first retry; each further retry doubles it.
+    pub base_delay: Duration,
+    /// Randomness applied to each delay.
+    pub jitter: JitterStrategy,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            max_retries_ceiling: DEFAULT_MAX_RETRIES_CEILING,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            jitter: JitterStrategy::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the retry count for a request: its own `requested` value if
+    /// given, otherwise the default, never exceeding the ceiling.
+    pub fn retries_for(&self, requested: Option<u32>) -> u32 {
+        requested
+            .unwrap_or(self.max_retries)
+            .min(self.max_retries_ceiling)
+    }
+
+    /// Returns how long to wait before retry number `attempt` (starting at 0).
+    /// The exponential delay `base_delay * 2^attempt`, capped at ten seconds, is
+    /// jittered using `sample`, a uniform random value in `[0, 1)`. All backoff
+    /// timing is computed here.
+    pub fn backoff_delay(&self, attempt: u32, sample: f64) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        let delay = self.base_delay.saturating_mul(factor).min(MAX_BACKOFF);
+        let sample = sample.clamp(0.0, 1.0);
+
+        match self.jitter {
+            JitterStrategy::Full => delay.mul_f64(sample),
+            JitterStrategy::Equal => delay / 2 + (delay / 2).mul_f64(sample),
+            JitterStrategy::None => delay,
+        }
+    }
+
+    /// Returns how long to wait before retry number `attempt`, or `None` to
+    /// stop retrying. A `Retry-After` from the failed response is honored as
+    /// is; one beyond the ten-second cap ends the retries, leaving the wait to
+    /// the caller.
+    pub fn retry_delay(
+        &self,
+        attempt: u32,
+        sample: f64,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        match retry_after {
+            Some(retry_after) => (retry_after <= MAX_BACKOFF).then_some(retry_after),
+            None => Some(self.backoff_delay(attempt, sample)),
+        }
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds. HTTP-date values aren't
+/// supported and yield `None`.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Returns a uniform random value in `[0, 1)` for [`RetryPolicy::backoff_delay`].
+/// Each `RandomState` is randomly keyed, which is plenty for jitter.
+pub fn jitter_sample() -> f64 {
+    let bits = RandomState::new().hash_one(0u64);
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a fresh `Idempotency-Key` value for one logical search: 32 random
+/// hex digits. Every retry of the search sends the same key, so a backend that
+/// honours it processes the search at most once.
+pub fn idempotency_key() -> String {
+    let state = RandomState::new();
+    format!("{:016x}{:016x}", state.hash_one(0u64), state.hash_one(1u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_for_respects_ceiling() {
+        let policy = RetryPolicy {
+            max_retries: 1,
+            max_retries_ceiling: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.retries_for(None), 1);
+        assert_eq!(policy.retries_for(Some(0)), 0);
+        assert_eq!(policy.retries_for(Some(2)), 2);
+        assert_eq!(policy.retries_for(Some(50)), 3);
+    }
+
+    fn policy(jitter: JitterStrategy) -> RetryPolicy {
+        RetryPolicy {
+            jitter,
+            ..Default::default()
+        }
+    }
+
+    /// Deterministic samples spanning `[0, 1)`, from a fixed-seed SplitMix64.
+    fn samples(seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        let mut samples = vec![0.0, 0.999_999];
+        for _ in 0..100 {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            samples.push(((z ^ (z >> 31)) >> 11) as f64 / (1u64 << 53) as f64);
+        }
+        samples
+    }
+
+    #[test]
+    fn test_no_jitter_doubles_up_to_cap() {
+        let policy = policy(JitterStrategy::None);
+
+        assert_eq!(policy.backoff_delay(0, 0.3), Duration::from_millis(250));
+        assert_eq!(policy.backoff_delay(2, 0.9), Duration::from_millis(1000));
+        assert_eq!(policy.backoff_delay(40, 0.5), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_full_jitter_bounds() {
+        let policy = policy(JitterStrategy::Full);
+        let full = Duration::from_millis(1000);
+
+        for sample in samples(42) {
+            let delay = policy.backoff_delay(2, sample);
+            assert!(delay < full, "{:?}", delay);
+        }
+        assert_eq!(policy.backoff_delay(2, 0.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_equal_jitter_bounds() {
+        let policy = policy(JitterStrategy::Equal);
+        let full = Duration::from_millis(1000);
+
+        for sample in samples(42) {
+            let delay = policy.backoff_delay(2, sample);
+            assert!(delay >= full / 2 && delay < full, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_jitter_sample_range() {
+        for _ in 0..100 {
+            let sample = jitter_sample();
+            assert!((0.0..1.0).contains(&sample));
+        }
+    }
+
+    #[test]
+    fn test_jitter_strategy_from_str() {
+        assert_eq!("full".parse(), Ok(JitterStrategy::Full));
+        assert_eq!(" Equal ".parse(), Ok(JitterStrategy::Equal));
+        assert_eq!("none".parse(), Ok(JitterStrategy::None));
+        assert!("random".parse::<JitterStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_retry_delay_honors_retry_after() {
+        let policy = policy(JitterStrategy::None);
+
+        assert_eq!(
+            policy.retry_delay(0, 0.5, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policy.retry_delay(1, 0.5, None),
+            Some(policy.base_delay * 2)
+        );
+        assert_eq!(
+            policy.retry_delay(0, 0.5, Some(Duration::from_secs(60))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("0"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+    }
+
+    #[test]
+    fn test_idempotency_keys_are_unique_hex() {
+        let key = idempotency_key();
+        assert_eq!(key.len(), 32);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(key, idempotency_key());
+    }
+}
@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// What happens to control characters in a query or system instructions,
+/// from `PERPLEXICA_CONTROL_CHARS`. Tabs and newlines are always allowed, and
+/// carriage returns are always turned into newlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlChars {
+    /// Drop them and send the rest.
+    #[default]
+    Strip,
+    /// Fail the call with an invalid-parameter error.
+    Reject,
+}
+
+impl ControlChars {
+    pub const VALID_VALUES: &'static [&'static str] = &["strip", "reject"];
+}
+
+impl FromStr for ControlChars {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "strip" => Ok(Self::Strip),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!(
+                "PERPLEXICA_CONTROL_CHARS must be one of {}, got '{}'",
+                Self::VALID_VALUES.join(", "),
+                value
+            )),
+        }
+    }
+}
+
+/// Removes or rejects the control characters in `text` per `mode`. `\r\n`
+/// and a lone `\r` become `\n`. Clean text is borrowed back unchanged; with
+/// [`ControlChars::Reject`] the first disallowed character is the error.
+pub fn sanitize(text: &str, mode: ControlChars) -> Result<Cow<'_, str>, char> {
+    let disallowed = |c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r');
+    if !text.chars().any(|c| c == '\r' || disallowed(c)) {
+        return Ok(Cow::Borrowed(text));
+    }
+    if mode == ControlChars::Reject
+        && let Some(c) = text.chars().find(|&c| disallowed(c))
+    {
+        return Err(c);
+    }
+
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                sanitized.push('\n');
+            }
+            c if disallowed(c) => {}
+            c => sanitized.push(c),
+        }
+    }
+    Ok(Cow::Owned(sanitized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_input_is_unchanged() {
+        for text in ["What is Rust?", "tabs\tand\nnewlines", "ünïcödé ✓", ""] {
+            for mode in [ControlChars::Strip, ControlChars::Reject] {
+                let sanitized = sanitize(text, mode).unwrap();
+                assert!(matches!(sanitized, Cow::Borrowed(_)), "{:?}", text);
+                assert_eq!(sanitized, text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_null_bytes() {
+        assert_eq!(
+            sanitize("what\0 is\u{7}\u{1b} rust", ControlChars::Strip).unwrap(),
+            "what is rust"
+        );
+        assert_eq!(sanitize("what\0 is rust", ControlChars::Reject), Err('\0'));
+    }
+
+    #[test]
+    fn test_carriage_returns_become_newlines() {
+        for mode in [ControlChars::Strip, ControlChars::Reject] {
+            assert_eq!(
+                sanitize("one\r\ntwo\rthree\n", mode).unwrap(),
+                "one\ntwo\nthree\n"
+            );
+        }
+        assert_eq!(sanitize("one\r\n\0two", ControlChars::Reject), Err('\0'));
+    }
+
+    #[test]
+    fn test_control_chars_from_str() {
+        assert_eq!("strip".parse(), Ok(ControlChars::Strip));
+        assert_eq!(" Reject ".parse(), Ok(ControlChars::Reject));
+        assert!("allow".parse::<ControlChars>().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Default upper bound on how long shutdown waits for in-flight requests.
+pub const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 10;
+
+/// Tracks the upstream requests currently in progress so shutdown can wait
+/// for them, and whether shutdown has begun, so new searches are refused.
+#[derive(Debug, Default)]
+pub struct InFlight {
+    next_id: AtomicU64,
+    requests: Mutex<HashMap<u64, (&'static str, Instant)>>,
+    idle: Notify,
+    closing: AtomicBool,
+}
+
+impl InFlight {
+    /// Registers a request; it stays in flight until the guard is dropped.
+    pub fn start(self: &Arc<Self>, kind: &'static str) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(id, (kind, Instant::now()));
+        InFlightGuard {
+            in_flight: self.clone(),
+            id,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.lock().unwrap().is_empty()
+    }
+
+    /// Whether shutdown has begun.
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Relaxed)
+    }
+
+    async fn wait_idle(&self) {
+        loop {
+            // Register interest before checking so a wakeup between the check
+            // and the await is not lost.
+            let notified = self.idle.notified();
+            if self.is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut requests = self.in_flight.requests.lock().unwrap();
+        requests.remove(&self.id);
+        if requests.is_empty() {
+            self.in_flight.idle.notify_waiters();
+        }
+    }
+}
+
+/// What shutdown needs from the service once it has been handed to the
+/// transport: the in-flight tracker and the grace period.
+#[derive(Debug, Clone)]
+pub struct Drain {
+    pub in_flight: Arc<InFlight>,
+    pub grace: Duration,
+}
+
+impl Drain {
+    /// Marks the service as closing, then waits up to the grace period for
+    /// in-flight requests to finish. Returns `false`, after logging each
+    /// abandoned request, if any were still running.
+    ///
+    /// Run it while the transport is still up, so the results of the requests
+    /// it waits for still reach the client.
+    pub async fn run(&self) -> bool {
+        self.in_flight.closing.store(true, Ordering::Relaxed);
+        if tokio::time::timeout(self.grace, self.in_flight.wait_idle())
+            .await
+            .is_ok()
+        {
+            return true;
+        }
+
+        for (kind, started) in self.in_flight.requests.lock().unwrap().values() {
+            tracing::warn!(
+                request = kind,
+                running_ms = started.elapsed().as_millis() as u64,
+                "abandoning in-flight request at shutdown"
+            );
+        }
+        false
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on Unix.
+pub async fn signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(in_flight: &Arc<InFlight>, grace_ms: u64) -> Drain {
+        Drain {
+            in_flight: in_flight.clone(),
+            grace: Duration::from_millis(grace_ms),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_returns_once_requests_finish() {
+        let in_flight = Arc::new(InFlight::default());
+        assert!(drain(&in_flight, 0).run().await);
+
+        let guard = in_flight.start("search");
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(guard);
+        });
+
+        assert!(drain(&in_flight, 5_000).run().await);
+        assert!(in_flight.is_empty());
+        assert!(in_flight.is_closing());
+    }
+
+    #[tokio::test]
+    async fn test_drain_gives_up_after_grace_period() {
+        let in_flight = Arc::new(InFlight::default());
+        let _hung = in_flight.start("search");
+
+        let started = Instant::now();
+        let finished = drain(&in_flight, 50).run().await;
+
+        assert!(!finished);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(in_flight.requests.lock().unwrap().len(), 1);
+    }
+}
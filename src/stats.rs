@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in seconds, of the search latency histogram's buckets.
+pub const LATENCY_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Counters for what the server has done since it started, reported by
+/// `perplexica_stats`. Nothing is persisted; a restart resets them.
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+    searches: AtomicU64,
+    /// Failed searches per error kind, as in the error data.
+    failures: Mutex<BTreeMap<String, u64>>,
+    provider_listings: AtomicU64,
+    /// Searches per latency bucket, the last one for those slower than every
+    /// bound.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_micros: AtomicU64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            searches: AtomicU64::new(0),
+            failures: Mutex::new(BTreeMap::new()),
+            provider_listings: AtomicU64::new(0),
+            latency_buckets: Default::default(),
+            latency_sum_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A point-in-time copy of [`Stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSnapshot {
+    pub uptime: Duration,
+    pub searches: u64,
+    pub failures: BTreeMap<String, u64>,
+    pub provider_listings: u64,
+    /// Searches per bucket of [`LATENCY_BUCKETS`], not cumulative; the last
+    /// entry counts searches slower than every bound.
+    pub latency_buckets: Vec<u64>,
+    pub latency_sum: Duration,
+}
+
+impl StatsSnapshot {
+    pub fn failed(&self) -> u64 {
+        self.failures.values().sum()
+    }
+
+    pub fn succeeded(&self) -> u64 {
+        self.searches.saturating_sub(self.failed())
+    }
+}
+
+impl Stats {
+    /// Counts one search that took `elapsed`, and its failure when
+    /// `error_kind` is set.
+    pub fn record_search(&self, error_kind: Option<&str>, elapsed: Duration) {
+        self.searches.fetch_add(1, Ordering::Relaxed);
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| elapsed.as_secs_f64() <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if let Some(kind) = error_kind {
+            *self
+                .failures
+                .lock()
+                .unwrap()
+                .entry(kind.to_string())
+                .or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_provider_listing(&self) {
+        self.provider_listings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            uptime: self.started.elapsed(),
+            searches: self.searches.load(Ordering::Relaxed),
+            failures: self.failures.lock().unwrap().clone(),
+            provider_listings: self.provider_listings.load(Ordering::Relaxed),
+            latency_buckets: self
+                .latency_buckets
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            latency_sum: Duration::from_micros(self.latency_sum_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_searches_failures_and_listings() {
+        let stats = Stats::default();
+        stats.record_search(None, Duration::from_millis(50));
+        stats.record_search(Some("upstream_status"), Duration::from_millis(300));
+        stats.record_search(Some("upstream_status"), Duration::from_secs(1));
+        stats.record_search(Some("request_failed"), Duration::from_secs(600));
+        stats.record_provider_listing();
+
+        let snapshot = stats.snapshot();
+
+        assert_eq!(snapshot.searches, 4);
+        assert_eq!(snapshot.succeeded(), 1);
+        assert_eq!(snapshot.failed(), 3);
+        assert_eq!(
+            snapshot.failures,
+            BTreeMap::from([
+                ("request_failed".to_string(), 1),
+                ("upstream_status".to_string(), 2),
+            ])
+        );
+        assert_eq!(snapshot.provider_listings, 1);
+        assert_eq!(
+            snapshot.latency_buckets,
+            vec![1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(snapshot.latency_sum, Duration::from_millis(601_350));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::perplexica_service::{PerplexicaSearchResponse, Source};
+use serde::Deserialize;
+
+/// One line of a streamed search: `{"type": "...", "data": ...}`.
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+/// Builds a search response from the newline-delimited events Perplexica sends
+/// when `stream` is set: `sources` carries the source list, each `response`
+/// event a piece of the message, and `done` marks the end. Other events, such
+/// as the initial `init`, are ignored.
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    /// Bytes of a line that hasn't been terminated yet.
+    pending: Vec<u8>,
+    message: String,
+    sources: Vec<Source>,
+    done: bool,
+    /// The message of an `error` event, which ends the stream early.
+    error: Option<String>,
+}
+
+impl StreamAccumulator {
+    /// Feeds the next chunk of the body, which may end mid-line. Fails on a
+    /// complete line that isn't a valid event.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), String> {
+        self.pending.extend_from_slice(chunk);
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.apply(&line)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, line: &[u8]) -> Result<(), String> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() || self.done || self.error.is_some() {
+            return Ok(());
+        }
+
+        let event: StreamEvent = serde_json::from_str(line)
+            .map_err(|e| format!("invalid stream event '{}': {}", line, e))?;
+        match event.kind.as_str() {
+            "response" => {
+                if let Some(text) = event.data.as_str() {
+                    self.message.push_str(text);
+                }
+            }
+            "sources" => {
+                self.sources = serde_json::from_value(event.data)
+                    .map_err(|e| format!("invalid sources event: {}", e))?;
+            }
+            "done" => self.done = true,
+            "error" => {
+                self.error = Some(match event.data {
+                    serde_json::Value::String(message) => message,
+                    data => data.to_string(),
+                })
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Ends the stream, with `interruption` describing the read error if the
+    /// body was cut off. A stream that stops before `done` still yields the
+    /// message received so far, marked with [`PerplexicaSearchResponse::interrupted`];
+    /// only when no text arrived at all is the reason returned as an error.
+    pub fn finish(
+        mut self,
+        interruption: Option<String>,
+    ) -> Result<PerplexicaSearchResponse, String> {
+        let last_line = std::mem::take(&mut self.pending);
+        let last_line = self.apply(&last_line);
+        // A cut-off body can end in half an event; that is the interruption's
+        // doing rather than a malformed stream.
+        if interruption.is_none() {
+            last_line?;
+        }
+
+        let interrupted = interruption
+            .or(self.error)
+            .or_else(|| (!self.done).then(|| "stream ended before completion".to_string()));
+        if let Some(reason) = &interrupted
+            && self.message.is_empty()
+        {
+            return Err(reason.clone());
+        }
+
+        Ok(PerplexicaSearchResponse {
+            message: self.message,
+            sources: self.sources,
+            interrupted,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = concat!(
+        r#"{"type":"init","data":"Stream connected"}"#,
+        "\n",
+        r#"{"type":"sources","data":[{"pageContent":"","metadata":{"title":"Rust","url":"https://www.rust-lang.org"}}]}"#,
+        "\n",
+        r#"{"type":"response","data":"Rust is "}"#,
+        "\n",
+        r#"{"type":"response","data":"a systems "}"#,
+        "\n",
+        r#"{"type":"response","data":"language [1]."}"#,
+        "\n",
+        r#"{"type":"done"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_accumulates_complete_stream_across_chunk_boundaries() {
+        let mut accumulator = StreamAccumulator::default();
+        for chunk in EVENTS.as_bytes().chunks(7) {
+            accumulator.push(chunk).unwrap();
+        }
+
+        let response = accumulator.finish(None).unwrap();
+
+        assert_eq!(response.message, "Rust is a systems language [1].");
+        assert_eq!(response.sources.len(), 1);
+        assert_eq!(response.sources[0].metadata.title, "Rust");
+        assert!(response.interrupted.is_none());
+    }
+
+    #[test]
+    fn test_interrupted_stream_keeps_partial_message() {
+        let cut = EVENTS.find("language").unwrap();
+        let mut accumulator = StreamAccumulator::default();
+        accumulator.push(&EVENTS.as_bytes()[..cut]).unwrap();
+
+        let response = accumulator
+            .finish(Some("connection reset".to_string()))
+            .unwrap();
+
+        assert_eq!(response.message, "Rust is a systems ");
+        assert_eq!(response.sources.len(), 1);
+        assert_eq!(response.interrupted.as_deref(), Some("connection reset"));
+    }
+
+    #[test]
+    fn test_stream_without_text_or_done_is_an_error() {
+        let mut accumulator = StreamAccumulator::default();
+        accumulator
+            .push(b"{\"type\":\"init\",\"data\":\"Stream connected\"}\n")
+            .unwrap();
+        assert_eq!(
+            accumulator
+                .finish(Some("connection reset".to_string()))
+                .unwrap_err(),
+            "connection reset"
+        );
+
+        let mut accumulator = StreamAccumulator::default();
+        accumulator
+            .push(b"{\"type\":\"response\",\"data\":\"Partial\"}\n")
+            .unwrap();
+        let response = accumulator.finish(None).unwrap();
+        assert_eq!(
+            response.interrupted.as_deref(),
+            Some("stream ended before completion")
+        );
+
+        let mut accumulator = StreamAccumulator::default();
+        let error = accumulator.push(b"<html>\n").unwrap_err();
+        assert!(error.contains("invalid stream event"), "{}", error);
+    }
+}
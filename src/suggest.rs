@@ -0,0 +1,316 @@
+use crate::perplexica_service::{Model, Provider, ProvidersResponse};
+
+/// Task words asking for a quick answer.
+const SPEED_TASK_WORDS: &[&str] = &[
+    "fast", "quick", "quickly", "lookup", "simple", "cheap", "short", "factual",
+];
+/// Task words asking for careful, long-form work.
+const QUALITY_TASK_WORDS: &[&str] = &[
+    "long",
+    "synthesis",
+    "synthesize",
+    "deep",
+    "detailed",
+    "thorough",
+    "report",
+    "complex",
+    "reasoning",
+    "analysis",
+];
+
+/// Model name words marking a small, fast model.
+const FAST_MODEL_WORDS: &[&str] = &["mini", "flash", "haiku", "nano", "small", "lite", "tiny"];
+/// Model name words marking a large, high-quality model.
+const LARGE_MODEL_WORDS: &[&str] = &["opus", "large", "pro", "ultra", "max"];
+
+/// Parameter counts, in billions, at or below which a model counts as fast and
+/// at or above which it counts as large, for names like `llama3:8b`.
+const FAST_MAX_BILLIONS: f64 = 13.0;
+const LARGE_MIN_BILLIONS: f64 = 65.0;
+
+/// What a task needs most from a chat model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Speed,
+    Balanced,
+    Quality,
+}
+
+impl Priority {
+    /// Reads the priority from words in `task`; no task, or one without
+    /// either kind of word, is balanced.
+    pub fn for_task(task: Option<&str>) -> Self {
+        let words = words(task.unwrap_or_default());
+        let has_any = |list: &[&str]| words.iter().any(|word| list.contains(&word.as_str()));
+        match (has_any(SPEED_TASK_WORDS), has_any(QUALITY_TASK_WORDS)) {
+            (true, false) => Self::Speed,
+            (false, true) => Self::Quality,
+            _ => Self::Balanced,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Speed => "speed",
+            Self::Balanced => "a balance of speed and quality",
+            Self::Quality => "quality",
+        }
+    }
+}
+
+/// How big a chat model looks from its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelTier {
+    Fast,
+    Standard,
+    Large,
+}
+
+impl ModelTier {
+    /// Classifies a model by the words in its key and display name, and by a
+    /// parameter count such as `8b` or `70b`.
+    pub fn of(model: &Model) -> Self {
+        let words = words(&format!("{} {}", model.key, model.name));
+        for word in &words {
+            if let Some(billions) = parameter_billions(word) {
+                if billions <= FAST_MAX_BILLIONS {
+                    return Self::Fast;
+                }
+                if billions >= LARGE_MIN_BILLIONS {
+                    return Self::Large;
+                }
+            }
+        }
+
+        if words
+            .iter()
+            .any(|word| FAST_MODEL_WORDS.contains(&word.as_str()))
+        {
+            Self::Fast
+        } else if words
+            .iter()
+            .any(|word| LARGE_MODEL_WORDS.contains(&word.as_str()))
+        {
+            Self::Large
+        } else {
+            Self::Standard
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Fast => "a small, fast model",
+            Self::Standard => "a general-purpose model",
+            Self::Large => "a large, high-quality model",
+        }
+    }
+
+    fn fit(self, priority: Priority) -> u8 {
+        match (priority, self) {
+            (Priority::Speed, Self::Fast)
+            | (Priority::Quality, Self::Large)
+            | (Priority::Balanced, Self::Standard) => 2,
+            (Priority::Speed | Priority::Quality, Self::Standard) | (Priority::Balanced, _) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// The chat model recommended for a task, with an embedding model from the
+/// same provider.
+#[derive(Debug)]
+pub struct ModelSuggestion<'a> {
+    pub priority: Priority,
+    pub provider: &'a Provider,
+    pub chat_model: &'a Model,
+    pub tier: ModelTier,
+    pub embedding_model: Option<&'a Model>,
+    /// Other chat models that fit as well, as `provider_id/key`.
+    pub alternatives: Vec<String>,
+}
+
+/// Upper bound on the alternatives listed with a suggestion.
+const MAX_ALTERNATIVES: usize = 3;
+
+/// Picks the chat model whose tier best fits `task`, preferring providers
+/// that also offer an embedding model, since a search needs both from one
+/// provider. Ties go to the first model listed. `None` when there are no chat
+/// models at all.
+pub fn suggest_models<'a>(
+    providers: &'a ProvidersResponse,
+    task: Option<&str>,
+) -> Option<ModelSuggestion<'a>> {
+    let priority = Priority::for_task(task);
+    let with_embeddings = providers
+        .providers
+        .iter()
+        .any(|provider| !provider.embedding_models.is_empty());
+
+    let mut candidates: Vec<(u8, &Provider, &Model, ModelTier)> = providers
+        .providers
+        .iter()
+        .filter(|provider| !with_embeddings || !provider.embedding_models.is_empty())
+        .flat_map(|provider| {
+            provider.chat_models.iter().map(move |model| {
+                let tier = ModelTier::of(model);
+                (tier.fit(priority), provider, model, tier)
+            })
+        })
+        .collect();
+    // Stable, so equally good models keep their listing order.
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.0));
+
+    let (best_fit, provider, chat_model, tier) = *candidates.first()?;
+    let alternatives = candidates[1..]
+        .iter()
+        .filter(|(fit, ..)| *fit == best_fit)
+        .take(MAX_ALTERNATIVES)
+        .map(|(_, provider, model, _)| format!("{}/{}", provider.id, model.key))
+        .collect();
+
+    Some(ModelSuggestion {
+        priority,
+        provider,
+        chat_model,
+        tier,
+        embedding_model: provider.embedding_models.first(),
+        alternatives,
+    })
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '.')
+        .map(|word| word.trim_matches('.'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Parses a parameter count word such as `8b` or `1.5b`.
+fn parameter_billions(word: &str) -> Option<f64> {
+    word.strip_suffix('b')?
+        .parse()
+        .ok()
+        .filter(|billions: &f64| *billions > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(key: &str, name: &str) -> Model {
+        Model {
+            name: name.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    fn providers() -> ProvidersResponse {
+        ProvidersResponse {
+            providers: vec![
+                Provider {
+                    id: "openai".to_string(),
+                    name: "OpenAI".to_string(),
+                    chat_models: vec![
+                        model("gpt-4o", "GPT 4o"),
+                        model("gpt-4o-mini", "GPT 4o Mini"),
+                    ],
+                    embedding_models: vec![model("text-embedding-3-small", "Embedding Small")],
+                },
+                Provider {
+                    id: "ollama".to_string(),
+                    name: "Ollama".to_string(),
+                    chat_models: vec![
+                        model("llama3:8b", "Llama 3 8B"),
+                        model("llama3:70b", "Llama 3 70B"),
+                    ],
+                    embedding_models: vec![model("nomic-embed-text", "Nomic Embed")],
+                },
+                Provider {
+                    id: "anthropic".to_string(),
+                    name: "Anthropic".to_string(),
+                    chat_models: vec![model("claude-3-opus", "Claude 3 Opus")],
+                    embedding_models: vec![],
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_priority_for_task() {
+        assert_eq!(
+            Priority::for_task(Some("fast factual lookup")),
+            Priority::Speed
+        );
+        assert_eq!(
+            Priority::for_task(Some("long-form synthesis")),
+            Priority::Quality
+        );
+        assert_eq!(
+            Priority::for_task(Some("quick but detailed")),
+            Priority::Balanced
+        );
+        assert_eq!(Priority::for_task(None), Priority::Balanced);
+    }
+
+    #[test]
+    fn test_model_tier() {
+        assert_eq!(ModelTier::of(&model("gpt-4o-mini", "")), ModelTier::Fast);
+        assert_eq!(
+            ModelTier::of(&model("gemini-1.5-flash", "")),
+            ModelTier::Fast
+        );
+        assert_eq!(ModelTier::of(&model("llama3:8b", "")), ModelTier::Fast);
+        assert_eq!(ModelTier::of(&model("llama3:70b", "")), ModelTier::Large);
+        assert_eq!(ModelTier::of(&model("claude-3-opus", "")), ModelTier::Large);
+        assert_eq!(
+            ModelTier::of(&model("mistral", "Mistral Large")),
+            ModelTier::Large
+        );
+        assert_eq!(
+            ModelTier::of(&model("gpt-4o", "GPT 4o")),
+            ModelTier::Standard
+        );
+        // A version number is not a parameter count.
+        assert_eq!(ModelTier::of(&model("gemini-1.5", "")), ModelTier::Standard);
+    }
+
+    #[test]
+    fn test_suggest_models_for_speed() {
+        let providers = providers();
+
+        let suggestion = suggest_models(&providers, Some("fast factual lookup")).unwrap();
+
+        assert_eq!(suggestion.priority, Priority::Speed);
+        assert_eq!(suggestion.provider.id, "openai");
+        assert_eq!(suggestion.chat_model.key, "gpt-4o-mini");
+        assert_eq!(suggestion.tier, ModelTier::Fast);
+        assert_eq!(
+            suggestion.embedding_model.unwrap().key,
+            "text-embedding-3-small"
+        );
+        assert_eq!(suggestion.alternatives, ["ollama/llama3:8b"]);
+    }
+
+    #[test]
+    fn test_suggest_models_for_quality_skips_providers_without_embeddings() {
+        let providers = providers();
+
+        let suggestion = suggest_models(&providers, Some("long-form synthesis")).unwrap();
+
+        // claude-3-opus fits too, but its provider can't embed.
+        assert_eq!(suggestion.provider.id, "ollama");
+        assert_eq!(suggestion.chat_model.key, "llama3:70b");
+        assert!(suggestion.alternatives.is_empty());
+    }
+
+    #[test]
+    fn test_suggest_models_balanced_and_empty() {
+        let providers = providers();
+        let suggestion = suggest_models(&providers, None).unwrap();
+        assert_eq!(suggestion.chat_model.key, "gpt-4o");
+
+        assert!(suggest_models(&ProvidersResponse::default(), None).is_none());
+    }
+}
@@ -0,0 +1,339 @@
+//! Helpers shared by the unit tests: a minimal HTTP/1.1 mock server, an
+//! in-memory MCP session, and an in-memory environment for constructing
+//! services without touching the process environment.
+
+use crate::perplexica_service::PerplexicaService;
+use rmcp::ServiceExt;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::io::{Lines, ReadHalf, WriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub delay: Option<Duration>,
+    /// Announce more body than is sent, so the client sees the connection
+    /// drop part way through.
+    pub cut_off: bool,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: None,
+            cut_off: false,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    pub fn cut_off(mut self) -> Self {
+        self.cut_off = true;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync;
+
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    concurrency: Arc<Concurrency>,
+}
+
+/// Requests currently being handled and the most seen at once.
+#[derive(Default)]
+struct Concurrency {
+    active: AtomicUsize,
+    peak: AtomicUsize,
+    /// Requests the client hung up on while their response was delayed.
+    aborted: AtomicUsize,
+}
+
+impl MockServer {
+    pub async fn start(
+        handler: impl Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let concurrency = Arc::new(Concurrency::default());
+
+        let recorded = requests.clone();
+        let tracked = concurrency.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                let tracked = tracked.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, &handler, &recorded, &tracked).await;
+                });
+            }
+        });
+
+        Self {
+            url,
+            requests,
+            concurrency,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// The most requests that were being handled at the same time.
+    pub fn peak_concurrency(&self) -> usize {
+        self.concurrency.peak.load(Ordering::SeqCst)
+    }
+
+    /// How many delayed requests the client gave up on before the response
+    /// was sent.
+    pub fn aborted(&self) -> usize {
+        self.concurrency.aborted.load(Ordering::SeqCst)
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    handler: &Arc<Handler>,
+    recorded: &Arc<Mutex<Vec<RecordedRequest>>>,
+    concurrency: &Concurrency,
+) -> Option<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break position;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    let request = RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    };
+    recorded.lock().unwrap().push(request.clone());
+
+    let active = concurrency.active.fetch_add(1, Ordering::SeqCst) + 1;
+    concurrency.peak.fetch_max(active, Ordering::SeqCst);
+    let response = handler(&request);
+    if let Some(delay) = response.delay {
+        // The client sends nothing more, so a read finishing means it closed
+        // the connection.
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            _ = stream.read(&mut chunk) => {
+                concurrency.active.fetch_sub(1, Ordering::SeqCst);
+                concurrency.aborted.fetch_add(1, Ordering::SeqCst);
+                return None;
+            }
+        }
+    }
+    concurrency.active.fetch_sub(1, Ordering::SeqCst);
+
+    let mut raw = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len() + if response.cut_off { 1024 } else { 0 }
+    );
+    for (name, value) in &response.headers {
+        raw.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    raw.push_str("\r\n");
+    raw.push_str(&response.body);
+
+    stream.write_all(raw.as_bytes()).await.ok()?;
+    stream.flush().await.ok()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        302 => "Found",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Status",
+    }
+}
+
+/// A client speaking raw JSON-RPC to a service over an in-memory transport, for
+/// tests that need to go through the MCP protocol layer.
+pub struct McpSession {
+    reader: Lines<BufReader<ReadHalf<DuplexStream>>>,
+    writer: WriteHalf<DuplexStream>,
+    next_id: u64,
+    /// Notifications the server sent while requests were waiting for replies.
+    notifications: Vec<Value>,
+}
+
+impl McpSession {
+    /// Serves `service` and completes the initialize handshake.
+    pub async fn start(service: PerplexicaService) -> Self {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            if let Ok(running) = service.serve(tokio::io::split(server)).await {
+                let _ = running.waiting().await;
+            }
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut session = Self {
+            reader: BufReader::new(reader).lines(),
+            writer,
+            next_id: 0,
+            notifications: Vec::new(),
+        };
+        session
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": {},
+                    "clientInfo": { "name": "test", "version": "0" }
+                }),
+            )
+            .await;
+        session
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        session
+    }
+
+    /// Sends a request and returns the full JSON-RPC response, with either a
+    /// `result` or an `error`.
+    pub async fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.start_request(method, params).await;
+        self.response(id).await
+    }
+
+    /// Sends a request without waiting for the reply, returning its id.
+    pub async fn start_request(&mut self, method: &str, params: Value) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await;
+        id
+    }
+
+    /// Waits for the reply to request `id`.
+    pub async fn response(&mut self, id: u64) -> Value {
+        loop {
+            let line = self.reader.next_line().await.unwrap().unwrap();
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == id {
+                return message;
+            }
+            if message.get("id").is_none() {
+                self.notifications.push(message);
+            }
+        }
+    }
+
+    pub async fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await;
+    }
+
+    pub fn notifications(&self) -> &[Value] {
+        &self.notifications
+    }
+
+    async fn send(&mut self, message: Value) {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+}
+
+/// Builds an environment lookup backed by the given key/value pairs.
+pub fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> + use<> {
+    let vars: HashMap<String, String> = pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    move |key| vars.get(key).cloned()
+}
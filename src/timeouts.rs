@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Per-mode search timeouts from `PERPLEXICA_TIMEOUT_OVERRIDES`.
+///
+/// The variable is a comma-separated list of `mode=seconds` entries, where a
+/// mode is a focus mode or an optimization mode. A search without a matching
+/// entry keeps the client-wide timeout; when both its focus and optimization
+/// mode match, the longer timeout applies.
+#[derive(Debug, Clone, Default)]
+pub struct TimeoutOverrides(HashMap<String, Duration>);
+
+impl TimeoutOverrides {
+    /// Parses the variable, accepting only the modes in `known_modes`.
+    pub fn parse(value: Option<&str>, known_modes: &[&str]) -> Result<Self, String> {
+        let mut overrides = HashMap::new();
+
+        for entry in value.unwrap_or_default().split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (mode, seconds) = entry.split_once('=').ok_or_else(|| {
+                format!(
+                    "PERPLEXICA_TIMEOUT_OVERRIDES entry '{}' must be mode=seconds",
+                    entry
+                )
+            })?;
+            let mode = mode.trim();
+            if !known_modes.contains(&mode) {
+                return Err(format!(
+                    "PERPLEXICA_TIMEOUT_OVERRIDES mode '{}' must be one of {}",
+                    mode,
+                    known_modes.join(", ")
+                ));
+            }
+            let seconds = seconds
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|&seconds| seconds > 0)
+                .ok_or_else(|| {
+                    format!(
+                        "PERPLEXICA_TIMEOUT_OVERRIDES timeout for '{}' must be a positive number of seconds, got '{}'",
+                        mode,
+                        seconds.trim()
+                    )
+                })?;
+
+            overrides.insert(mode.to_string(), Duration::from_secs(seconds));
+        }
+
+        Ok(Self(overrides))
+    }
+
+    /// The timeout for a search, or `None` to keep the client-wide one.
+    pub fn for_search(&self, focus_mode: &str, optimization_mode: &str) -> Option<Duration> {
+        [focus_mode, optimization_mode]
+            .into_iter()
+            .filter_map(|mode| self.0.get(mode).copied())
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: &[&str] = &["webSearch", "academicSearch", "speed", "quality"];
+
+    #[test]
+    fn test_parse_overrides() {
+        let overrides =
+            TimeoutOverrides::parse(Some("academicSearch=120, quality = 90,,"), MODES).unwrap();
+
+        assert_eq!(overrides.0.len(), 2);
+        assert_eq!(overrides.0["academicSearch"], Duration::from_secs(120));
+        assert_eq!(overrides.0["quality"], Duration::from_secs(90));
+        assert!(TimeoutOverrides::parse(None, MODES).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_entries() {
+        let error = TimeoutOverrides::parse(Some("academicSearch"), MODES).unwrap_err();
+        assert!(error.contains("must be mode=seconds"), "{}", error);
+
+        let error = TimeoutOverrides::parse(Some("imageSearch=30"), MODES).unwrap_err();
+        assert!(error.contains("'imageSearch' must be one of"), "{}", error);
+
+        for seconds in ["0", "-5", "soon"] {
+            let entry = format!("quality={}", seconds);
+            let error = TimeoutOverrides::parse(Some(&entry), MODES).unwrap_err();
+            assert!(error.contains("positive number of seconds"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_timeout_for_search() {
+        let overrides =
+            TimeoutOverrides::parse(Some("academicSearch=120,quality=90,speed=10"), MODES).unwrap();
+
+        assert_eq!(
+            overrides.for_search("academicSearch", "speed"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            overrides.for_search("webSearch", "quality"),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            overrides.for_search("academicSearch", "quality"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(overrides.for_search("webSearch", "balanced"), None);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::metrics::METRICS_PATH;
+use crate::perplexica_service::{PerplexicaService, parse_flag};
+use futures::{SinkExt, StreamExt};
+use rmcp::{
+    RoleServer, ServiceExt,
+    service::{RxJsonRpcMessage, TxJsonRpcMessage},
+};
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+/// Where the WebSocket transport listens unless `PERPLEXICA_WS_BIND` says
+/// otherwise. Loopback only, since the server has no authentication of its own.
+pub const DEFAULT_WS_BIND: &str = "127.0.0.1:8765";
+
+/// Longest `/metrics` request head read before answering.
+const MAX_METRICS_REQUEST_BYTES: u64 = 8 * 1024;
+
+/// How the MCP protocol is served, from `PERPLEXICA_TRANSPORT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// JSON-RPC lines on stdin and stdout, for clients that spawn the server.
+    Stdio,
+    /// One JSON-RPC message per WebSocket text frame, on `addr`. With
+    /// `metrics`, plain HTTP requests for `/metrics` are answered on the same
+    /// address.
+    Ws { addr: SocketAddr, metrics: bool },
+}
+
+impl Transport {
+    pub const VALID_VALUES: &'static [&'static str] = &["stdio", "ws"];
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let transport = lookup("PERPLEXICA_TRANSPORT").unwrap_or_default();
+        let metrics = parse_flag(lookup("PERPLEXICA_METRICS"), "PERPLEXICA_METRICS")?;
+        match transport.trim().to_ascii_lowercase().as_str() {
+            "" | "stdio" if metrics => {
+                anyhow::bail!("PERPLEXICA_METRICS needs PERPLEXICA_TRANSPORT=ws")
+            }
+            "" | "stdio" => Ok(Self::Stdio),
+            "ws" => {
+                let bind = lookup("PERPLEXICA_WS_BIND")
+                    .filter(|bind| !bind.trim().is_empty())
+                    .unwrap_or_else(|| DEFAULT_WS_BIND.to_string());
+                let addr = bind.trim().parse().map_err(|_| {
+                    anyhow::anyhow!(
+                        "PERPLEXICA_WS_BIND must be an address and port such as {}, got '{}'",
+                        DEFAULT_WS_BIND,
+                        bind
+                    )
+                })?;
+                Ok(Self::Ws { addr, metrics })
+            }
+            _ => anyhow::bail!(
+                "PERPLEXICA_TRANSPORT must be one of {}, got '{}'",
+                Self::VALID_VALUES.join(", "),
+                transport
+            ),
+        }
+    }
+}
+
+/// Accepts WebSocket connections until `shutdown` resolves, serving each with
+/// its own clone of `service`; the clones share caches, limits and stats.
+/// Open connections then stay up while in-flight requests drain, so their
+/// results are still delivered, and are closed when this returns. Returns
+/// whether the drain finished within the grace period.
+pub async fn serve_ws(
+    listener: TcpListener,
+    service: PerplexicaService,
+    metrics: bool,
+    shutdown: impl Future<Output = ()>,
+) -> bool {
+    let drain = service.drain();
+    let mut connections = tokio::task::JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    let service = service.clone();
+                    connections.spawn(async move {
+                        if let Err(error) = serve_connection(stream, service, metrics).await {
+                            tracing::warn!(%peer, %error, "WebSocket connection failed");
+                        }
+                    });
+                }
+                Err(error) => tracing::warn!(%error, "failed to accept a WebSocket connection"),
+            },
+            // Reap finished connections so the set doesn't grow unbounded.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            () = &mut shutdown => break,
+        }
+    }
+    drop(listener);
+    drain.run().await
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    service: PerplexicaService,
+    metrics: bool,
+) -> anyhow::Result<()> {
+    if metrics && is_metrics_request(&stream).await? {
+        return serve_metrics(stream, &service).await;
+    }
+    let socket = tokio_tungstenite::accept_async(stream).await?;
+    let (sink, stream) = socket.split();
+
+    let sink = Box::pin(
+        sink.with(|message: TxJsonRpcMessage<RoleServer>| async move {
+            let text = serde_json::to_string(&message)
+                .map_err(|error| tungstenite::Error::Io(error.into()))?;
+            Ok::<_, tungstenite::Error>(Message::text(text))
+        }),
+    );
+    // Control frames are answered by tungstenite itself; a read error ends
+    // the session like a close would.
+    let stream = Box::pin(
+        stream
+            .take_while(|message| std::future::ready(message.is_ok()))
+            .filter_map(|message| async move {
+                let Ok(Message::Text(text)) = message else {
+                    return None;
+                };
+                match serde_json::from_str::<RxJsonRpcMessage<RoleServer>>(&text) {
+                    Ok(message) => Some(message),
+                    Err(error) => {
+                        tracing::warn!(%error, "ignoring a WebSocket frame that isn't a JSON-RPC message");
+                        None
+                    }
+                }
+            }),
+    );
+
+    let running = service.serve((sink, stream)).await?;
+    running.waiting().await?;
+    Ok(())
+}
+
+/// Whether the connection opens with a `GET /metrics` request line, without
+/// consuming it so a WebSocket handshake can still read it. The request line
+/// arrives in the first packet in practice, so one peek is enough.
+async fn is_metrics_request(stream: &TcpStream) -> std::io::Result<bool> {
+    let prefix = format!("GET {}", METRICS_PATH);
+    let mut head = vec![0; prefix.len() + 1];
+    let read = stream.peek(&mut head).await?;
+    Ok(
+        read == head.len()
+            && head.starts_with(prefix.as_bytes())
+            && b" ?".contains(&head[read - 1]),
+    )
+}
+
+/// Answers a `/metrics` scrape and closes the connection.
+async fn serve_metrics(stream: TcpStream, service: &PerplexicaService) -> anyhow::Result<()> {
+    let mut stream = BufReader::new(stream.take(MAX_METRICS_REQUEST_BYTES));
+    // Read the request head so closing doesn't reset the connection.
+    let mut line = String::new();
+    while stream.read_line(&mut line).await? > 0 && !line.trim_end().is_empty() {
+        line.clear();
+    }
+
+    let body = service.metrics();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let stream = stream.get_mut().get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PerplexicaConfig;
+    use crate::test_support::{MockResponse, MockServer, env};
+    use serde_json::{Value, json};
+    use std::time::Duration;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    #[test]
+    fn test_transport_from_lookup() {
+        assert_eq!(Transport::from_lookup(env(&[])).unwrap(), Transport::Stdio);
+        assert_eq!(
+            Transport::from_lookup(env(&[("PERPLEXICA_TRANSPORT", " WS ")])).unwrap(),
+            Transport::Ws {
+                addr: DEFAULT_WS_BIND.parse().unwrap(),
+                metrics: false
+            }
+        );
+        assert_eq!(
+            Transport::from_lookup(env(&[
+                ("PERPLEXICA_TRANSPORT", "ws"),
+                ("PERPLEXICA_WS_BIND", "0.0.0.0:9000"),
+                ("PERPLEXICA_METRICS", "1"),
+            ]))
+            .unwrap(),
+            Transport::Ws {
+                addr: "0.0.0.0:9000".parse().unwrap(),
+                metrics: true
+            }
+        );
+
+        for (vars, error) in [
+            (
+                &[("PERPLEXICA_TRANSPORT", "sse")][..],
+                "must be one of stdio, ws, got 'sse'",
+            ),
+            (
+                &[
+                    ("PERPLEXICA_TRANSPORT", "ws"),
+                    ("PERPLEXICA_WS_BIND", "localhost"),
+                ][..],
+                "PERPLEXICA_WS_BIND must be an address and port",
+            ),
+            (
+                &[("PERPLEXICA_METRICS", "1")][..],
+                "PERPLEXICA_METRICS needs PERPLEXICA_TRANSPORT=ws",
+            ),
+        ] {
+            let message = Transport::from_lookup(env(vars)).unwrap_err().to_string();
+            assert!(message.contains(error), "{}", message);
+        }
+    }
+
+    /// Serves `vars` over WebSocket on a free port, returning its address.
+    async fn start(vars: &[(&str, &str)], metrics: bool) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service =
+            PerplexicaService::with_config(PerplexicaConfig::from_iter(vars.iter().copied()))
+                .unwrap();
+        tokio::spawn(serve_ws(listener, service, metrics, std::future::pending()));
+        addr
+    }
+
+    /// Sends `message`, returning the response to it, or `Null` for a
+    /// notification.
+    async fn request(socket: &mut Socket, message: Value) -> Value {
+        socket
+            .send(Message::text(message.to_string()))
+            .await
+            .unwrap();
+        if message.get("id").is_none() {
+            return Value::Null;
+        }
+        loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn connect(addr: SocketAddr) -> (Socket, Value) {
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        let initialized = request(
+            &mut socket,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": {},
+                    "clientInfo": { "name": "test", "version": "0" }
+                }
+            }),
+        )
+        .await;
+        request(
+            &mut socket,
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        )
+        .await;
+        (socket, initialized)
+    }
+
+    async fn call_tool(socket: &mut Socket, id: u64, name: &str, arguments: Value) -> Value {
+        request(
+            socket,
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_ws_session_initializes_and_calls_a_tool() {
+        let addr = start(&[("PERPLEXICA_API_URL", "http://localhost:3000")], false).await;
+
+        let (mut socket, initialized) = connect(addr).await;
+        assert_eq!(initialized["id"], 1);
+        assert!(initialized["result"]["capabilities"]["tools"].is_object());
+
+        let called = call_tool(&mut socket, 2, "perplexica_version", json!({})).await;
+        assert_eq!(called["id"], 2);
+        let text = called["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains(crate::version::VERSION), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_slow_search_finishes_during_shutdown_drain() {
+        let backend = MockServer::start(|_| {
+            MockResponse::new(200, r#"{"message": "Slow answer.", "sources": []}"#)
+                .with_delay(Duration::from_millis(300))
+        })
+        .await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = PerplexicaService::with_config(PerplexicaConfig::from_iter([
+            ("PERPLEXICA_API_URL", backend.url()),
+            ("PERPLEXICA_PROVIDER_ID", "openai"),
+            ("PERPLEXICA_CHAT_MODEL_KEY", "gpt-4o-mini"),
+            ("PERPLEXICA_EMBEDDING_MODEL_KEY", "text-embedding-3-small"),
+            ("PERPLEXICA_SHUTDOWN_GRACE_SECS", "5"),
+        ]))
+        .unwrap();
+        let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_ws(listener, service, false, async {
+            let _ = signal.await;
+        }));
+        let (mut socket, _) = connect(addr).await;
+
+        let search = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "perplexica_search", "arguments": { "query": "What is AI?" } }
+        });
+        socket
+            .send(Message::text(search.to_string()))
+            .await
+            .unwrap();
+        while backend.hits() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.send(()).unwrap();
+
+        // A search started after shutdown began is refused...
+        let refused = call_tool(
+            &mut socket,
+            3,
+            "perplexica_search",
+            json!({ "query": "What is ML?" }),
+        )
+        .await;
+        assert_eq!(refused["error"]["data"]["kind"], "shutting_down");
+        // ...while the one in flight still delivers its result.
+        let finished = loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                break serde_json::from_str::<Value>(&text).unwrap();
+            }
+        };
+        assert_eq!(finished["id"], 2);
+        let text = finished["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("Slow answer."), "{}", text);
+
+        assert!(server.await.unwrap());
+        assert_eq!(backend.hits(), 1);
+    }
+
+    async fn scrape(addr: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_after_searches() {
+        let addr = start(
+            &[
+                ("PERPLEXICA_MOCK", "1"),
+                ("PERPLEXICA_PROVIDER_ID", "mock"),
+                ("PERPLEXICA_CHAT_MODEL_KEY", "mock-chat"),
+                ("PERPLEXICA_EMBEDDING_MODEL_KEY", "mock-embedding"),
+            ],
+            true,
+        )
+        .await;
+        let (mut socket, _) = connect(addr).await;
+        for (id, focus_mode) in [(2, "webSearch"), (3, "webSearch"), (4, "noSuchMode")] {
+            call_tool(
+                &mut socket,
+                id,
+                "perplexica_search",
+                json!({ "query": "What is Rust?", "focus_mode": focus_mode }),
+            )
+            .await;
+        }
+
+        let response = scrape(addr).await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{}", head);
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+
+        let value = |name: &str| -> f64 {
+            body.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("no {} in\n{}", name, body))
+                .parse()
+                .unwrap()
+        };
+        assert_eq!(value("perplexica_searches_total"), 3.0);
+        assert_eq!(
+            value("perplexica_search_errors_total{kind=\"invalid_param\"}"),
+            1.0
+        );
+        assert_eq!(value("perplexica_search_duration_seconds_count"), 3.0);
+        assert_eq!(
+            value("perplexica_search_duration_seconds_bucket{le=\"+Inf\"}"),
+            3.0
+        );
+        assert!(value("perplexica_search_duration_seconds_sum") > 0.0);
+
+        // Without PERPLEXICA_METRICS, /metrics is just a failed handshake.
+        let addr = start(&[("PERPLEXICA_MOCK", "1")], false).await;
+        assert!(!scrape(addr).await.contains("perplexica_searches_total"));
+    }
+}
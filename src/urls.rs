@@ -0,0 +1,134 @@
+use crate::perplexica_service::{PerplexicaSearchResponse, SourceMetadata};
+use std::borrow::Cow;
+
+/// Source metadata fields that may name the site a relative URL belongs to,
+/// as a full URL or a bare host.
+const BASE_FIELDS: &[&str] = &["baseUrl", "base_url", "host", "domain"];
+
+/// Expands a protocol-relative (`//example.com/page`) or root-relative
+/// (`/page`) URL into an absolute `https://` one. A root-relative URL needs
+/// `base`, a URL or bare host naming the site; without a usable one, and for
+/// anything else, `url` is returned untouched.
+pub fn absolute_url<'a>(url: &'a str, base: Option<&str>) -> Cow<'a, str> {
+    let trimmed = url.trim();
+    if let Some(rest) = trimmed.strip_prefix("//") {
+        return if rest.is_empty() {
+            Cow::Borrowed(url)
+        } else {
+            Cow::Owned(format!("https://{}", rest))
+        };
+    }
+    if !trimmed.starts_with('/') {
+        return Cow::Borrowed(url);
+    }
+
+    let Some(base) = base.map(str::trim).filter(|base| !base.is_empty()) else {
+        return Cow::Borrowed(url);
+    };
+    let base = if base.contains("://") {
+        Cow::Borrowed(base)
+    } else {
+        Cow::Owned(format!("https://{}", base.trim_start_matches('/')))
+    };
+    match reqwest::Url::parse(&base).and_then(|base| base.join(trimmed)) {
+        Ok(absolute) if absolute.has_host() => Cow::Owned(absolute.to_string()),
+        _ => Cow::Borrowed(url),
+    }
+}
+
+/// The site a source's relative URL belongs to, from the first of
+/// [`BASE_FIELDS`] its metadata carries as a string.
+fn source_base(metadata: &SourceMetadata) -> Option<&str> {
+    BASE_FIELDS
+        .iter()
+        .find_map(|field| metadata.extra.get(*field)?.as_str())
+}
+
+/// Rewrites relative source URLs with [`absolute_url`]. Returns `None` when
+/// every URL was already absolute or couldn't be expanded.
+pub fn expand_source_urls(resp: &PerplexicaSearchResponse) -> Option<PerplexicaSearchResponse> {
+    let mut expanded = None;
+    for (index, source) in resp.sources.iter().enumerate() {
+        let metadata = &source.metadata;
+        if let Cow::Owned(url) = absolute_url(&metadata.url, source_base(metadata)) {
+            let resp: &mut PerplexicaSearchResponse = expanded.get_or_insert_with(|| resp.clone());
+            resp.sources[index].metadata.url = url;
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_protocol_relative_urls_get_https() {
+        assert_eq!(
+            absolute_url("//example.com/page?q=1", None),
+            "https://example.com/page?q=1"
+        );
+        assert_eq!(
+            absolute_url("//cdn.example.com/a.png", Some("http://example.com")),
+            "https://cdn.example.com/a.png"
+        );
+        assert_eq!(absolute_url("//", None), "//");
+    }
+
+    #[test]
+    fn test_root_relative_urls_need_a_base() {
+        assert_eq!(
+            absolute_url("/docs/intro", Some("example.com")),
+            "https://example.com/docs/intro"
+        );
+        assert_eq!(
+            absolute_url("/docs/intro", Some("https://example.com/blog/post")),
+            "https://example.com/docs/intro"
+        );
+        assert_eq!(absolute_url("/docs/intro", None), "/docs/intro");
+        assert_eq!(absolute_url("/docs/intro", Some(" ")), "/docs/intro");
+        assert_eq!(
+            absolute_url("/docs/intro", Some("not a host")),
+            "/docs/intro"
+        );
+    }
+
+    #[test]
+    fn test_absolute_and_other_urls_are_untouched() {
+        for url in [
+            "https://example.com/page",
+            "http://example.com/page",
+            "example.com/page",
+            "",
+        ] {
+            assert!(matches!(
+                absolute_url(url, Some("example.org")),
+                Cow::Borrowed(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_expand_source_urls_uses_metadata_base() {
+        let resp: PerplexicaSearchResponse = serde_json::from_value(json!({
+            "message": "See [1], [2] and [3].",
+            "sources": [
+                { "pageContent": "", "metadata": { "title": "A", "url": "//a.example/x" } },
+                { "pageContent": "", "metadata": { "title": "B", "url": "/y", "host": "b.example" } },
+                { "pageContent": "", "metadata": { "title": "C", "url": "/z" } },
+            ]
+        }))
+        .unwrap();
+
+        let expanded = expand_source_urls(&resp).unwrap();
+
+        let urls: Vec<_> = expanded
+            .sources
+            .iter()
+            .map(|source| source.metadata.url.as_str())
+            .collect();
+        assert_eq!(urls, ["https://a.example/x", "https://b.example/y", "/z"]);
+        assert!(expand_source_urls(&expanded).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::perplexica_service::PerplexicaSearchResponse;
+use futures::StreamExt;
+use std::fmt;
+use std::time::Duration;
+
+/// Upper bound on each source check, redirects included.
+pub const TIMEOUT: Duration = Duration::from_secs(3);
+/// Source checks one search runs at once.
+const CONCURRENCY: usize = 8;
+
+/// Whether a source URL answered a `HEAD` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reachability {
+    /// The final HTTP status, after redirects.
+    Status(u16),
+    /// The request failed or timed out, or the URL isn't http(s).
+    Unreachable,
+}
+
+impl fmt::Display for Reachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(status) => write!(f, "HTTP {}", status),
+            Self::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
+
+/// Sends a `HEAD` request to each source URL, at most [`CONCURRENCY`] at a
+/// time, and returns what each answered, by source index. A failed check is
+/// reported as [`Reachability::Unreachable`], never as an error.
+///
+/// The client must not carry the Perplexica API token or cookie, since the
+/// URLs point at arbitrary sites.
+pub async fn check_sources(
+    client: &reqwest::Client,
+    resp: &PerplexicaSearchResponse,
+) -> Vec<Reachability> {
+    // Owned futures, so the stream's type borrows nothing and stays `Send`
+    // inside the tool's future.
+    let checks: Vec<_> = resp
+        .sources
+        .iter()
+        .map(|source| check_url(client.clone(), source.metadata.url.clone()))
+        .collect();
+    futures::stream::iter(checks)
+        .buffered(CONCURRENCY)
+        .collect()
+        .await
+}
+
+async fn check_url(client: reqwest::Client, url: String) -> Reachability {
+    let Ok(url) = reqwest::Url::parse(url.trim()) else {
+        return Reachability::Unreachable;
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Reachability::Unreachable;
+    }
+    match client.head(url).send().await {
+        Ok(response) => Reachability::Status(response.status().as_u16()),
+        Err(error) => {
+            tracing::debug!(error = %error, "source check failed");
+            Reachability::Unreachable
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_check_sources_reports_each_status() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/gone" => MockResponse::new(404, ""),
+            "/slow" => MockResponse::new(200, "").with_delay(Duration::from_secs(5)),
+            _ => MockResponse::new(200, ""),
+        })
+        .await;
+        let resp: PerplexicaSearchResponse = serde_json::from_value(json!({
+            "message": "",
+            "sources": [
+                { "pageContent": "", "metadata": { "title": "A", "url": format!("{}/ok", server.url()) } },
+                { "pageContent": "", "metadata": { "title": "B", "url": format!("{}/gone", server.url()) } },
+                { "pageContent": "", "metadata": { "title": "C", "url": format!("{}/slow", server.url()) } },
+                { "pageContent": "", "metadata": { "title": "D", "url": "/relative" } },
+                { "pageContent": "", "metadata": { "title": "E", "url": "ftp://example.com/file" } },
+            ]
+        }))
+        .unwrap();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let checks = check_sources(&client, &resp).await;
+
+        assert_eq!(
+            checks,
+            [
+                Reachability::Status(200),
+                Reachability::Status(404),
+                Reachability::Unreachable,
+                Reachability::Unreachable,
+                Reachability::Unreachable,
+            ]
+        );
+        assert_eq!(server.hits(), 3);
+        assert!(
+            server
+                .requests()
+                .iter()
+                .all(|request| request.method == "HEAD")
+        );
+        assert_eq!(checks[1].to_string(), "HTTP 404");
+    }
+}
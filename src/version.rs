@@ -0,0 +1,19 @@
+/// The crate version, set by Cargo.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The target triple the binary was built for.
+pub const TARGET: &str = env!("PERPLEXICA_BUILD_TARGET");
+
+/// The git commit the binary was built from, when built from a checkout.
+pub fn git_commit() -> Option<&'static str> {
+    Some(env!("PERPLEXICA_GIT_COMMIT")).filter(|commit| !commit.is_empty())
+}
+
+/// One line per fact, for `--version` and `perplexica_version`.
+pub fn build_info() -> String {
+    let mut info = format!("{} {}\nTarget: {}", env!("CARGO_PKG_NAME"), VERSION, TARGET);
+    if let Some(commit) = git_commit() {
+        info.push_str("\nCommit: ");
+        info.push_str(commit);
+    }
+    info
+}
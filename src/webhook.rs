@@ -0,0 +1,100 @@
+use crate::shutdown::InFlight;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Delivery attempts per result, including the first.
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; each further retry doubles it.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+/// Upper bound on each delivery attempt.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Posts search results to `PERPLEXICA_RESULT_WEBHOOK` in the background.
+///
+/// The client must not carry the Perplexica API token. Delivery never delays or
+/// fails the tool call: it runs on its own task, and failures are only logged,
+/// without the URL since webhook URLs often embed a secret.
+#[derive(Debug)]
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(url: String, client: reqwest::Client) -> Self {
+        Self { url, client }
+    }
+
+    /// Starts delivering `payload` and returns immediately. The delivery counts
+    /// as in flight, so shutdown waits for it like any other request.
+    pub fn send(self: &Arc<Self>, in_flight: &Arc<InFlight>, payload: serde_json::Value) {
+        let webhook = self.clone();
+        let guard = in_flight.start("webhook");
+        tokio::spawn(async move {
+            let _guard = guard;
+            webhook.deliver(&payload).await;
+        });
+    }
+
+    async fn deliver(&self, payload: &serde_json::Value) -> bool {
+        for attempt in 0..MAX_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+            }
+
+            match self.client.post(&self.url).json(payload).send().await {
+                Ok(response) if response.status().is_success() => return true,
+                Ok(response) => tracing::warn!(
+                    attempt = attempt + 1,
+                    status = response.status().as_u16(),
+                    "result webhook rejected delivery"
+                ),
+                Err(error) => tracing::warn!(
+                    attempt = attempt + 1,
+                    error = %error.without_url(),
+                    "result webhook delivery failed"
+                ),
+            }
+        }
+
+        tracing::warn!(
+            attempts = MAX_ATTEMPTS,
+            "giving up on result webhook delivery"
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_delivery_retries_until_accepted() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let server = MockServer::start(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                MockResponse::new(503, "")
+            } else {
+                MockResponse::new(200, "")
+            }
+        })
+        .await;
+        let webhook = Webhook::new(server.url().to_string(), reqwest::Client::new());
+
+        assert!(webhook.deliver(&serde_json::json!({ "ok": true })).await);
+        assert_eq!(server.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_gives_up_after_max_attempts() {
+        let server = MockServer::start(|_| MockResponse::new(500, "")).await;
+        let webhook = Webhook::new(server.url().to_string(), reqwest::Client::new());
+
+        assert!(!webhook.deliver(&serde_json::json!({})).await);
+        assert_eq!(server.hits(), MAX_ATTEMPTS as usize);
+    }
+}